@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate criterion;
+extern crate rustlet;
+
+use criterion::Criterion;
+use rustlet::FIGfont;
+
+// Compare loading a font from its .flf file with loading the same font compiled
+// to the compact binary format, where FIGcharacter lines are borrowed from the data
+// instead of parsed. Both build the same character map.
+
+fn load(c: &mut Criterion) {
+    let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/standard.flf";
+    let data: &'static [u8] = Box::leak(FIGfont::from_path(&path).unwrap().compile().into_boxed_slice());
+
+    let mut group = c.benchmark_group("load");
+    group.bench_function("flf", |b| b.iter(|| FIGfont::from_path(&path).unwrap()));
+    group.bench_function("compiled", |b| b.iter(|| FIGfont::from_bytes(data).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
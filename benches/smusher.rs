@@ -0,0 +1,33 @@
+#[macro_use]
+extern crate criterion;
+extern crate rustlet;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use rustlet::{FIGfont, Smusher};
+
+// Push increasingly long messages into a smusher. The time spent on each input
+// character should remain the same as the output grows, so throughput must be
+// roughly constant across sizes if smushing scales linearly.
+
+fn push_str(c: &mut Criterion) {
+    let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/standard.flf";
+    let font = FIGfont::from_path(&path).unwrap();
+    let text = "The quick brown fox jumps over the lazy dog. ";
+
+    let mut group = c.benchmark_group("push_str");
+    for size in [100, 1000, 10000].iter() {
+        let msg: String = text.chars().cycle().take(*size).collect();
+        group.throughput(Throughput::Elements(*size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &msg, |b, msg| {
+            b.iter(|| {
+                let mut sm = Smusher::new(&font);
+                sm.push_str(msg);
+                sm.len()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, push_str);
+criterion_main!(benches);
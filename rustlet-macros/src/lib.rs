@@ -0,0 +1,116 @@
+//! Render FIGlet banners at compile time.
+//!
+//! The `figlet!` macro loads a FIGfont while the calling crate is compiled and expands
+//! to the rendered text as a `&'static str`, so no font is loaded at run time.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use] extern crate quote;
+extern crate rustlet;
+extern crate syn;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use proc_macro::TokenStream;
+use rustlet::{FIGfont, FontLocator, Smusher};
+use syn::parse::{Parse, ParseStream};
+use syn::{LitStr, Token};
+
+// Fonts are searched in this directory of the calling crate.
+const FONT_DIR: &str = "fonts";
+
+struct Args {
+    font: LitStr,   // font name or path
+    text: LitStr,   // text to render
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let font = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let text = input.parse()?;
+        if input.peek(Token![,]) {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(Args{ font, text })
+    }
+}
+
+/// Render text using a FIGfont at compile time.
+///
+/// The first argument is the font name, searched in the `fonts` directory of the
+/// calling crate, or the path to a font file relative to the crate root. The second
+/// argument is the text to render, which may contain newlines. The macro expands to
+/// a `&'static str` with the output rows separated by newlines, rendered with the font
+/// default layout mode.
+///
+/// # Examples
+///
+/// ```ignore
+/// #[macro_use] extern crate rustlet_macros;
+///
+/// const BANNER: &str = figlet!("standard", "Hello");
+///
+/// fn main() {
+///     println!("{}", BANNER);
+/// }
+/// ```
+///
+/// # Errors
+///
+/// If the font can't be found or loaded, a compile error naming the font and the
+/// reason is produced.
+///
+/// ```compile_fail
+/// #[macro_use] extern crate rustlet_macros;
+///
+/// const BANNER: &str = figlet!("nonexistent", "Hello");
+/// ```
+#[proc_macro]
+pub fn figlet(input: TokenStream) -> TokenStream {
+    match syn::parse::<Args>(input).and_then(|args| render(&args)) {
+        Ok(tokens) => tokens.into(),
+        Err(e)     => compile_error(&e).into(),
+    }
+}
+
+// syn's to_compile_error() refers to ::core, which doesn't resolve in 2015 edition crates.
+fn compile_error(e: &syn::Error) -> proc_macro2::TokenStream {
+    let msg = e.to_string();
+    quote_spanned!(e.span()=> compile_error!(#msg))
+}
+
+fn render(args: &Args) -> syn::Result<proc_macro2::TokenStream> {
+    let name = args.font.value();
+    let (path, font) = load(&name).map_err(|e| {
+        syn::Error::new(args.font.span(), format!("can't load font {}: {}", name, e))
+    })?;
+
+    let mut sm = Smusher::new(&font);
+    sm.push_str(&args.text.value());
+    let banner = sm.get().join("\n");
+
+    // include the font file so the banner is rendered again if the font changes
+    Ok(match path.to_str() {
+        Some(path) => quote!({
+            const _: &[u8] = include_bytes!(#path);
+            #banner
+        }),
+        None => quote!(#banner),
+    })
+}
+
+fn load(name: &str) -> Result<(PathBuf, FIGfont), rustlet::Error> {
+    let root = env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default();
+
+    // relative paths are resolved from the crate root, not the compiler working directory
+    let name = match Path::new(name).parent() {
+        Some(p) if !p.as_os_str().is_empty() => root.join(name).to_string_lossy().into_owned(),
+        _ => name.to_owned(),
+    };
+
+    let locator = FontLocator::with_dirs(&[root.join(FONT_DIR)]);
+    let path = locator.find(&name)?;
+    let font = FIGfont::from_path(&path)?;
+    Ok((path, font))
+}
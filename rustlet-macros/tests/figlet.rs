@@ -0,0 +1,35 @@
+#[macro_use] extern crate rustlet_macros;
+extern crate rustlet;
+
+use rustlet::{FIGfont, Smusher};
+
+const BANNER: &str = figlet!("../fonts/small.flf", "Hi");
+
+fn render(name: &str, text: &str) -> String {
+    let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/../fonts/" + name;
+    let font = FIGfont::from_path(&path).unwrap();
+    let mut sm = Smusher::new(&font);
+    sm.push_str(text);
+    sm.get().join("\n")
+}
+
+#[test]
+fn const_banner() {
+    assert_eq!(BANNER, render("small.flf", "Hi"));
+}
+
+#[test]
+fn newlines() {
+    assert_eq!(figlet!("../fonts/standard", "a\nb",), render("standard.flf", "a\nb"));
+}
+
+// resolved in the fonts directory of this crate
+#[test]
+fn bare_name() {
+    assert_eq!(figlet!("test", "abc"), "abc");
+}
+
+#[test]
+fn test_font() {
+    assert_eq!(figlet!("../tests/test.flf", "abc"), "abc");
+}
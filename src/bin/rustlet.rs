@@ -1,34 +1,48 @@
 extern crate getopts;
 extern crate rustlet;
-extern crate regex;
+#[cfg(unix)] extern crate libc;
 
 use std::env;
-use std::io::{self, BufRead};
-use std::path::{self, Path, PathBuf};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
 use getopts::{Matches, Options};
-use regex::Regex;
-use rustlet::Error;
+use rustlet::{Error, FIGfont, FontLocator, LayoutMode};
 
-const FONT_DIR     : &'static str = "/usr/share/figlet";
 const DEFAULT_FONT : &'static str = "standard.flf";
 const DEFAULT_WIDTH: usize = 80;
 
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+
     let mut opts = Options::new();
 
     opts.optflag("c", "center", "center the output horizontally");
+    opts.optopt("", "comment", "wrap output rows in comments (//, #, --, /*, ;;, <!--)", "style");
+    opts.optflag("", "compile", "compile the fonts given as arguments to .rlf files and exit");
     opts.optopt("d", "dir", "set the default font directory", "dir");
+    opts.optflag("", "fill", "pad output rows to the output width");
     opts.optopt("f", "font", "specify the figfont to use", "name");
+    opts.optopt("", "frame", "draw a frame around the output (ascii, single, double, rounded, heavy)", "style");
+    opts.optopt("", "frame-padding", "set the blank columns between the frame and the text", "cols");
+    opts.optopt("", "frame-title", "set the title shown in the frame", "text");
+    opts.optflag("", "fit", "use the largest font and layout that fit the output width");
     opts.optflag("h", "help", "display usage information and exit");
+    opts.optopt("", "height", "set the maximum output height when using --fit", "rows");
+    opts.optopt("I", "info", "print information and exit", "infocode");
     opts.optflag("k", "kern", "use kerning mode to display characters");
     opts.optflag("l", "left", "left-align the output");
-    opts.optopt("m", "mode", "override the font layout mode", "num");
+    opts.optflag("", "list-fonts", "list the fonts in the font search path and exit");
+    opts.optmulti("m", "mode", "override the font layout mode", "num");
     opts.optflag("o", "overlap", "use character overlapping mode");
+    opts.optopt("", "output", "set the directory to write compiled fonts", "dir");
     opts.optflag("p", "paragraph", "ignore mid-paragraph line breaks");
+    opts.optopt("", "preview", "render a sample text in each font and exit", "text");
     opts.optflag("r", "right", "right-align the output");
+    opts.optflag("s", "", "use the font default layout mode");
     opts.optflag("S", "smush", "use smushing mode to display characters");
+    opts.optflag("t", "terminal", "set the output width to the terminal width");
     opts.optflag("W", "full-width", "display characters in full width");
     opts.optopt("w", "width", "set the output width", "cols");
 
@@ -41,61 +55,95 @@ fn main() {
     };
 
     if matches.opt_present("h") {
-        let brief = format!("Usage: {} [options] message", args[0]);
+        let brief = format!("Usage: {0} [options] message\n       {0} --compile [--output dir] font...", args[0]);
         print!("{}", opts.usage(&brief));
         return;
     }
 
-    let mut fontpath = PathBuf::from(match matches.opt_str("d") {
-        Some(dir) => dir,
-        None      => FONT_DIR.to_string(),
-    });
-
-    match matches.opt_str("f") {
-        Some(name) => fontpath = find_font(fontpath, name),
-        None       => fontpath.push(DEFAULT_FONT),
+    // search the -d directory first and the current directory last
+    let mut locator = FontLocator::new();
+    if let Some(dir) = matches.opt_str("d") {
+        locator.dirs.insert(0, PathBuf::from(dir));
     }
+    let fontdir = locator.dirs[0].clone();
+    locator.dirs.push(PathBuf::from("."));
+
+    let fontname = matches.opt_str("f").unwrap_or_else(|| DEFAULT_FONT.to_string());
 
     let msg = matches.free.join(" ");
-    match run(&fontpath, &msg, &matches) {
-        Err(e) => { println!("Error: {}", e) }
+    let res = if let Some(code) = matches.opt_str("I") {
+        print_info(&code, &fontdir, &locator, &fontname, &matches)
+    } else if matches.opt_present("compile") {
+        compile_fonts(&locator, &matches)
+    } else if matches.opt_present("list-fonts") {
+        list_fonts(&locator, &matches)
+    } else if let Some(text) = matches.opt_str("preview") {
+        preview(&locator, &text, &matches)
+    } else if matches.opt_present("fit") {
+        // the layout is chosen by --fit, and paragraphs aren't fitted as a whole
+        if let Some(opt) = ["p", "k", "S", "s", "W", "o", "m"].iter().find(|x| matches.opt_present(x)) {
+            eprintln!("Error: -{} can't be used with --fit", opt);
+            return;
+        }
+        run_fit(&locator, &msg, &matches)
+    } else {
+        run(&locator, &fontname, &msg, &matches)
+    };
+
+    match res {
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::BrokenPipe => {},
+        Err(e) => { eprintln!("Error: {}", e) }
         Ok(_)  => {},
     }
 }
 
-fn find_font(mut fontpath: PathBuf, mut name: String) -> PathBuf {
-    if !name.ends_with(".flf") && !name.ends_with(".tlf") {
-        name = format!("{}.flf", name);
+// Print installation information, as in figlet. Unknown info codes print nothing.
+fn print_info(code: &str, dir: &Path, locator: &FontLocator, name: &str, matches: &Matches)
+              -> Result<(), Error> {
+    let version = env!("CARGO_PKG_VERSION");
+    match code.parse::<i32>()? {
+        0 => println!("rustlet version {}", version),
+        1 => {
+            let major: u32 = env!("CARGO_PKG_VERSION_MAJOR").parse()?;
+            let minor: u32 = env!("CARGO_PKG_VERSION_MINOR").parse()?;
+            let patch: u32 = env!("CARGO_PKG_VERSION_PATCH").parse()?;
+            println!("{}", major * 10000 + minor * 100 + patch);
+        },
+        2 => println!("{}", dir.display()),
+        3 => {
+            // the font must be valid to report its name
+            let path = locator.find(name)?;
+            FIGfont::from_path(&path)?;
+            println!("{}", font_name(&path));
+        },
+        4 => println!("{}", output_width(matches)?),
+        5 => println!("flf2 tlf2"),
+        _ => {},
     }
+    Ok(())
+}
 
-    if name.starts_with(path::MAIN_SEPARATOR) {
-        return PathBuf::from(&name);
-    }
+fn run(locator: &FontLocator, name: &str, msg: &str, matches: &Matches) -> Result<(), Error> {
+    let font = locator.load(name)?;
+    let wr = wrapper(&font, matches)?;
 
-    fontpath.push(&name);
-    if fontpath.exists() {
-        return fontpath;
-    }
+    let stdout = io::stdout();
+    render(rustlet::Renderer::new(wr, stdout.lock()), msg, matches.opt_present("p"))
+}
 
-    PathBuf::from(&name)
+// Create a wrapper for the given font using the layout, width and alignment options.
+fn wrapper<'a>(font: &'a FIGfont, matches: &Matches) -> Result<rustlet::Wrapper<&'a FIGfont>, Error> {
+    wrapper_with_mode(font, layout_mode(matches, font)?, matches)
 }
 
-fn run(path: &Path, msg: &str, matches: &Matches) -> Result<(), Error> {
-    let font = try!(rustlet::FIGfont::from_path(path));
-    let mut sm = rustlet::Smusher::new(&font);
+// Create a wrapper for the given font and layout mode using the width and alignment options.
+fn wrapper_with_mode<'a>(font: &'a FIGfont, mode: LayoutMode, matches: &Matches)
+                         -> Result<rustlet::Wrapper<&'a FIGfont>, Error> {
+    let mut sm = rustlet::Smusher::new(font);
 
-    if matches.opt_present("o") {
-        sm.mode = 0;
-    } else if matches.opt_present("k") {
-        sm.mode = rustlet::SMUSH_KERN;
-    } else if matches.opt_present("W") {
-        sm.full_width = true;
-    }
+    sm.mode = mode;
 
-    let width = match matches.opt_str("w") {
-        Some(s) => try!(s.parse::<usize>()),
-        None    => DEFAULT_WIDTH,
-    };
+    let width = output_width(matches)?;
 
     let mut wr = rustlet::Wrapper::new(sm, width);
 
@@ -105,46 +153,261 @@ fn run(path: &Path, msg: &str, matches: &Matches) -> Result<(), Error> {
         wr.align = rustlet::Align::Right;
     }
 
-    let re = Regex::new(r"(\S+|\s+)").unwrap();
+    if let Some(style) = matches.opt_str("frame") {
+        let mut frame = rustlet::Frame::new(style.parse()?);
+        if let Some(pad) = matches.opt_str("frame-padding") {
+            let pad = pad.parse()?;
+            frame.padding.left = pad;
+            frame.padding.right = pad;
+        }
+        frame.title = matches.opt_str("frame-title");
+        wr.frame = Some(frame);
+    }
+
+    wr.fill = matches.opt_present("fill");
+
+    if let Some(style) = matches.opt_str("comment") {
+        wr.comment = Some(style.parse()?);
+    }
+
+    Ok(wr)
+}
+
+// Find the fonts to list or preview: the font given with -f, or all fonts in the font
+// search path.
+fn font_paths(locator: &FontLocator, matches: &Matches) -> Result<Vec<PathBuf>, Error> {
+    if let Some(name) = matches.opt_str("f") {
+        return Ok(vec![locator.find(&name)?]);
+    }
+    Ok(font_files(locator))
+}
+
+// List the .flf, .tlf and .rlf files in the font search path, sorted by font name. Fonts
+// found more than once are listed once, using the file the locator would load.
+// Directories that can't be read are skipped.
+fn font_files(locator: &FontLocator) -> Vec<PathBuf> {
+    let mut names: Vec<String> = locator.dirs.iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|x| x.ok()).map(|x| x.path()))
+        .filter(|x| match x.extension() {
+            Some(ext) => ext == "flf" || ext == "tlf" || ext == "rlf",
+            None      => false,
+        })
+        .map(|x| font_name(&x))
+        .collect();
+    names.sort();
+    names.dedup();
+    names.iter().filter_map(|x| locator.find(x).ok()).collect()
+}
+
+// Load a font, reporting the reason on stderr if it can't be loaded.
+fn load_font(path: &Path) -> Option<FIGfont> {
+    match FIGfont::from_path(path) {
+        Ok(font) => Some(font),
+        Err(e)   => { eprintln!("{}: {}", path.display(), e); None },
+    }
+}
+
+// Compile fonts to the compact binary format, as in `rustlet --compile font...`.
+fn compile_fonts(locator: &FontLocator, matches: &Matches) -> Result<(), Error> {
+    let outdir = PathBuf::from(matches.opt_str("output").unwrap_or_else(|| ".".to_string()));
+    for name in &matches.free {
+        if let Err(e) = compile(locator, name, &outdir) {
+            eprintln!("{}: {}", name, e);
+        }
+    }
+    Ok(())
+}
+
+fn compile(locator: &FontLocator, name: &str, outdir: &Path) -> Result<(), Error> {
+    let path = locator.find(name)?;
+    let font = FIGfont::from_path(&path)?;
+
+    // zipped fonts have two extensions
+    let path = match path.extension() {
+        Some(ext) if ext == "zip" => path.with_extension(""),
+        _                         => path,
+    };
+    fs::write(outdir.join(font_name(&path) + ".rlf"), font.compile())?;
+    Ok(())
+}
+
+fn font_name(path: &Path) -> String {
+    path.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+fn list_fonts(locator: &FontLocator, matches: &Matches) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for path in font_paths(locator, matches)? {
+        if let Some(font) = load_font(&path) {
+            let format = match font.format {
+                rustlet::FontFormat::Flf => "flf",
+                rustlet::FontFormat::Tlf => "tlf",
+            };
+            let mode = LayoutMode::from_full_layout(font.layout);
+            writeln!(out, "{:<16} {:>3} {} {}", font_name(&path), font.height, format, mode)?;
+        }
+    }
+    Ok(())
+}
+
+fn preview(locator: &FontLocator, text: &str, matches: &Matches) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for path in font_paths(locator, matches)? {
+        if let Some(font) = load_font(&path) {
+            writeln!(out, "{}:", font_name(&path))?;
+            let mut r = rustlet::Renderer::new(wrapper(&font, matches)?, &mut out);
+            r.write_line(text)?;
+            r.flush()?;
+            writeln!(out)?;
+        }
+    }
+    Ok(())
+}
+
+// How layout options from the command line combine with the font layout mode.
+enum Override {
+    No,         // use the font layout mode
+    Yes,        // use the layout mode from the command line
+    Force,      // add the font layout mode to the command line layout mode
+}
+
+// Obtain the horizontal layout mode from the -k, -S, -s, -W, -o and -m options. As in
+// figlet, options are processed in the order they appear in the command line.
+fn layout_mode(matches: &Matches, font: &FIGfont) -> Result<LayoutMode, Error> {
+    let mut opts: Vec<(usize, &str, String)> = Vec::new();
+    for name in ["k", "S", "s", "W", "o"].iter() {
+        opts.extend(matches.opt_positions(name).into_iter().map(|x| (x, *name, String::new())));
+    }
+    opts.extend(matches.opt_strs_pos("m").into_iter().map(|(x, val)| (x, "m", val)));
+    opts.sort_by_key(|x| x.0);
+
+    let mut layout = 0;
+    let mut over = Override::No;
+    for (_, name, val) in opts {
+        let (l, o) = match name {
+            "k" => (rustlet::SMUSH_KERN, Override::Yes),
+            "S" => (rustlet::SMUSH_ENABLE, Override::Force),
+            "s" => (rustlet::SMUSH_ENABLE, Override::No),
+            "W" => (0, Override::Yes),
+            "o" => (rustlet::SMUSH_ENABLE, Override::Yes),
+            _   => (old_layout(&val)?.to_full_layout(), Override::Yes),
+        };
+        layout = l;
+        over = o;
+    }
+
+    Ok(LayoutMode::from_full_layout(match over {
+        Override::No    => font.layout,
+        Override::Yes   => layout,
+        Override::Force => layout | font.layout,
+    }))
+}
+
+// Parse the value of -m, naming the option and the accepted range if it's invalid.
+fn old_layout(val: &str) -> Result<LayoutMode, Error> {
+    val.parse().ok().and_then(|x| LayoutMode::from_old_layout(x).ok()).ok_or_else(|| {
+        let msg = format!("invalid layout mode for -m: {} (expected -1 to 63)", val);
+        Error::Io(io::Error::new(io::ErrorKind::InvalidInput, msg))
+    })
+}
 
-    if msg.len() > 0 {
+fn run_fit(locator: &FontLocator, msg: &str, matches: &Matches) -> Result<(), Error> {
+    let paths = font_files(locator);
+
+    // fonts that fail to load are not candidates
+    let fonts: Vec<FIGfont> = paths.iter().filter_map(|x| FIGfont::from_path(x).ok()).collect();
+    if fonts.is_empty() {
+        return Err(Error::Io(io::Error::new(io::ErrorKind::NotFound, "no fonts in font search path")));
+    }
+    let candidates: Vec<&FIGfont> = fonts.iter().collect();
+
+    // margins, frames and comments take room from the text
+    let width = wrapper(candidates[0], matches)?.text_width();
+    let height = match matches.opt_str("height") {
+        Some(s) => Some(s.parse::<usize>()?),
+        None    => None,
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut write_fit = |s: &str| -> Result<(), Error> {
+        if let Some(fit) = rustlet::fit(s, width, height, &candidates) {
+            // render with the chosen font and layout to apply alignment and decorations
+            let wr = wrapper_with_mode(candidates[fit.font], fit.mode, matches)?;
+            let mut r = rustlet::Renderer::new(wr, &mut out);
+            r.write_line(s)?;
+            r.flush()?;
+        }
+        Ok(())
+    };
+
+    if !msg.is_empty() {
         // read message from command line parameters
-        write_line(&mut wr, &msg, &re)
+        write_fit(msg)
     } else {
         // read message from stdin
         let input = io::BufReader::new(io::stdin());
-        if matches.opt_present("p") {
-            input.lines().for_each(|x| write_paragraph(&mut wr, &x.unwrap(), &re));
-            print_output(&wr.get());
-        } else {
-            input.lines().for_each(|x| write_line(&mut wr, &x.unwrap(), &re));
+        for line in input.lines() {
+            write_fit(&line?)?;
         }
+        Ok(())
     }
+}
 
-    Ok(())
+fn output_width(matches: &Matches) -> Result<usize, Error> {
+    if matches.opt_present("t") {
+        if let Some(width) = terminal_width() {
+            return Ok(width);
+        }
+    }
+    match matches.opt_str("w") {
+        Some(s) => Ok(s.parse::<usize>()?),
+        None    => Ok(DEFAULT_WIDTH),
+    }
 }
 
-fn write_line(wr: &mut rustlet::Wrapper, s: &str, re: &Regex) {
-    wr.clear();
-    write_tokens(wr, s, re);
-    print_output(&wr.get());
+// Obtain the width of the terminal attached to stdout, or the value of the COLUMNS
+// environment variable if stdout is not a terminal.
+fn terminal_width() -> Option<usize> {
+    tty_width().or_else(|| match env::var("COLUMNS").map(|x| x.parse::<usize>()) {
+        Ok(Ok(width)) if width > 0 => Some(width),
+        _                          => None,
+    })
 }
 
-fn write_paragraph(wr: &mut rustlet::Wrapper, s: &str, re: &Regex) {
-    if s.starts_with(char::is_whitespace) && !wr.is_empty() {
-        print_output(&wr.get());
-        wr.clear();
+#[cfg(unix)]
+fn tty_width() -> Option<usize> {
+    let mut ws = libc::winsize{ ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    match unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) } {
+        0 if ws.ws_col > 0 => Some(ws.ws_col as usize),
+        _                  => None,
     }
-    write_tokens(wr, s, re);
 }
 
-fn write_tokens(wr: &mut rustlet::Wrapper, s: &str, re: &Regex) {
-    re.captures_iter(s).for_each(|x| match x.get(0) {
-        Some(val) => wr.wrap_str(val.as_str(), &print_output),
-        None      => {},
-    });
+#[cfg(not(unix))]
+fn tty_width() -> Option<usize> {
+    None
 }
 
-fn print_output(v: &Vec<String>) {
-    v.iter().for_each(|x| println!("{}", x));
+fn render<W: Write>(mut r: rustlet::Renderer<&FIGfont, W>, msg: &str, paragraph: bool)
+                    -> Result<(), Error> {
+    if !msg.is_empty() {
+        // read message from command line parameters
+        r.write_line(msg)?;
+    } else {
+        // read message from stdin
+        let input = io::BufReader::new(io::stdin());
+        for line in input.lines() {
+            if paragraph {
+                r.write_paragraph(&line?)?;
+            } else {
+                r.write_line(&line?)?;
+            }
+        }
+    }
+
+    r.flush()
 }
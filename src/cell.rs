@@ -0,0 +1,222 @@
+use std::char;
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use Error;
+
+// FIGcharacter lines are laid out in terminal cells before smushing, so that smushing
+// and width computations work in display columns. Each grapheme cluster takes as many
+// cells as its display width (at least one), and is represented by a single char in
+// the first cell. Clusters made of more than one char are replaced by placeholders from
+// the Supplementary Private Use Area-B, and the remaining cells of wide clusters are
+// filled with a continuation marker. Sub-characters from this range found in the font
+// are also replaced by placeholders, so they can't be mistaken for either.
+
+// Marks the right half of a wide sub-character.
+pub(crate) const CONT: char = '\u{10FFFD}';
+
+// First placeholder for multi-char grapheme clusters.
+const FIRST_PLACEHOLDER: u32 = 0x100000;
+
+/// Placeholder table mapping placeholders to the grapheme clusters they replace. Each
+/// font has its own table, shared by all its FIGcharacters.
+#[derive(Debug, Default)]
+pub(crate) struct Clusters {
+    chars: HashMap<String, char>,   // placeholders by grapheme cluster
+    list : Vec<(String, usize)>,    // grapheme clusters and widths, by placeholder
+}
+
+impl Clusters {
+
+    // Get the placeholder for a grapheme cluster, adding it to the table if needed.
+    fn placeholder(&mut self, g: &str, width: usize) -> Result<char, Error> {
+        if let Some(&c) = self.chars.get(g) {
+            return Ok(c);
+        }
+        let code = FIRST_PLACEHOLDER + self.list.len() as u32;
+        if code >= CONT as u32 {
+            return Err(Error::FontFormat("too many grapheme clusters"));
+        }
+        // all code points in plane 16 are valid chars
+        let c = char::from_u32(code).unwrap();
+        self.chars.insert(g.to_owned(), c);
+        self.list.push((g.to_owned(), width));
+        Ok(c)
+    }
+
+    // Get the grapheme cluster and width replaced by a placeholder, if it is in the table.
+    fn get(&self, c: char) -> Option<(&str, usize)> {
+        let i = (c as u32).checked_sub(FIRST_PLACEHOLDER)? as usize;
+        self.list.get(i).map(|&(ref g, w)| (g.as_str(), w))
+    }
+}
+
+// Display width of a grapheme cluster, in cells.
+fn cluster_width(g: &str) -> usize {
+    g.width().max(1)
+}
+
+/// Compute the display width of a string in terminal columns, as used to lay out
+/// sub-characters (each grapheme cluster takes at least one column).
+pub(crate) fn str_width(s: &str) -> usize {
+    s.graphemes(true).map(cluster_width).sum()
+}
+
+/// Truncate a string to the given display width. A wide character that doesn't fit
+/// entirely is replaced with blanks.
+pub(crate) fn truncate(s: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut w = 0;
+    for g in s.graphemes(true) {
+        let gw = cluster_width(g);
+        if w + gw > width {
+            (w..width).for_each(|_| out.push(' '));
+            break;
+        }
+        out.push_str(g);
+        w += gw;
+    }
+    out
+}
+
+/// Lay out a FIGcharacter line in cells, adding placeholders to the given table. Returns
+/// None if each char already takes one cell, so the line can be used as it is.
+///
+/// If the table has no room for more placeholders, a FontFormat error is returned.
+pub(crate) fn to_cells(s: &str, table: &mut Clusters) -> Result<Option<String>, Error> {
+    if s.is_ascii() {
+        return Ok(None);
+    }
+
+    let mut out = String::new();
+    let mut changed = false;
+    for g in s.graphemes(true) {
+        let mut chars = g.chars();
+        let c = chars.next().unwrap();
+        let w = cluster_width(g);
+        if chars.next().is_some() || c as u32 >= FIRST_PLACEHOLDER {
+            out.push(table.placeholder(g, w)?);
+            changed = true;
+        } else {
+            out.push(c);
+        }
+        for _ in 1..w {
+            out.push(CONT);
+            changed = true;
+        }
+    }
+
+    Ok(if changed { Some(out) } else { None })
+}
+
+/// Convert a row of cells back to a string, replacing hardblanks with blanks and
+/// placeholders with their grapheme clusters from the table. Placeholders not in the
+/// table are kept unchanged. Halves of wide sub-characters that were broken by smushing
+/// or trimming become blanks, so the row keeps its width in columns.
+pub(crate) fn from_cells(cells: &[char], hardblank: char, table: &Clusters) -> String {
+    let special = |c: char| c as u32 >= FIRST_PLACEHOLDER;
+    if !cells.iter().any(|&c| special(c) || !c.is_ascii()) {
+        return cells.iter().map(|&c| if c == hardblank { ' ' } else { c }).collect();
+    }
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < cells.len() {
+        let c = cells[i];
+        let cluster = match special(c) && c != CONT {
+            true  => table.get(c),
+            false => None,
+        };
+        let (s, w) = match cluster {
+            Some((g, w))                      => (Some(g), w),
+            None if c == hardblank || c == CONT => (None, 1),
+            None                              => (None, cluster_width(c.encode_utf8(&mut [0; 4]))),
+        };
+
+        // a wide sub-character must be followed by all its continuation cells
+        let end = i + w;
+        if end > cells.len() || cells[i + 1..end].iter().any(|&x| x != CONT) {
+            out.push(' ');
+            i += 1;
+            continue;
+        }
+
+        match s {
+            Some(g)                          => out.push_str(g),
+            None if c == hardblank || c == CONT => out.push(' '),
+            None                             => out.push(c),
+        }
+        i = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_str_width() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("áé"), 2);
+        assert_eq!(str_width("e\u{301}e\u{301}"), 2);
+        assert_eq!(str_width("全角"), 4);
+        assert_eq!(str_width("a😀"), 3);
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abcd", 2), "ab");
+        assert_eq!(truncate("e\u{301}bc", 2), "e\u{301}b");
+        assert_eq!(truncate("全角", 3), "全 ");
+        assert_eq!(truncate("全角", 8), "全角");
+    }
+
+    #[test]
+    fn test_cells() {
+        let mut table = Clusters::default();
+        assert_eq!(to_cells("abc", &mut table).unwrap(), None);
+        assert_eq!(to_cells("áé", &mut table).unwrap(), None);
+
+        let cells: Vec<char> = to_cells("a全", &mut table).unwrap().unwrap().chars().collect();
+        assert_eq!(cells, vec!['a', '全', CONT]);
+        assert_eq!(from_cells(&cells, '$', &table), "a全");
+
+        let cells: Vec<char> = to_cells("e\u{301}$", &mut table).unwrap().unwrap().chars().collect();
+        assert_eq!(cells.len(), 2);
+        assert_eq!(from_cells(&cells, '$', &table), "e\u{301} ");
+    }
+
+    #[test]
+    fn test_private_use() {
+        // sub-characters in plane 16 are replaced by placeholders
+        let mut table = Clusters::default();
+        let cells: Vec<char> = to_cells("\u{10ABCD}\u{10FFFD}", &mut table).unwrap().unwrap().chars().collect();
+        assert_eq!(cells.len(), 2);
+        assert!(cells.iter().all(|&c| c != '\u{10ABCD}' && c != CONT));
+        assert_eq!(from_cells(&cells, '$', &table), "\u{10ABCD}\u{10FFFD}");
+
+        // unknown placeholders are kept
+        assert_eq!(from_cells(&['a', '\u{10ABCD}'], '$', &Clusters::default()), "a\u{10ABCD}");
+    }
+
+    #[test]
+    fn test_table_full() {
+        let mut table = Clusters::default();
+        for code in FIRST_PLACEHOLDER..CONT as u32 {
+            let s = char::from_u32(code).unwrap().to_string();
+            assert!(to_cells(&s, &mut table).is_ok());
+        }
+        assert!(matches!(to_cells("\u{10FFFD}", &mut table), Err(Error::FontFormat(_))));
+        assert!(to_cells("\u{100000}", &mut table).is_ok());
+    }
+
+    #[test]
+    fn test_broken_cells() {
+        // wide sub-characters missing a half are replaced with blanks
+        let table = Clusters::default();
+        assert_eq!(from_cells(&['全', 'x', CONT], '$', &table), " x ");
+        assert_eq!(from_cells(&['x', '全'], '$', &table), "x ");
+        assert_eq!(from_cells(&['a', '$', 'b'], '$', &table), "a b");
+    }
+}
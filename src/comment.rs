@@ -0,0 +1,143 @@
+use std::str::FromStr;
+use Error;
+#[cfg(feature = "serde")] use serde::{Deserialize, Serialize};
+
+/// Comment syntax used to embed output rows in source code.
+///
+/// Each row is wrapped in the comment delimiters, so block comments are opened and
+/// closed on every row. Sequences inside the row that would end the comment early
+/// are replaced with lookalikes of the same width: `/*` and `*/` become `/+` and `+/`
+/// in C block comments, and `--` becomes `-~` in HTML comments.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let font = rustlet::FIGfont::from_path("slant.flf")?;
+/// let mut wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 80);
+/// wr.comment = Some(rustlet::CommentStyle::CBlock);
+/// wr.fill = true;
+///
+/// // Rows are 80 columns wide, including the comment delimiters
+/// wr.push_str("Setup")?;
+/// let rows = wr.get();
+/// for line in wr.finish(&rows)? {
+///     println!("{}", line);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CommentStyle {
+    /// `//` line comments, as in C++, Rust, Go and Java.
+    Slashes,
+    /// `#` line comments, as in shell, Python and Perl.
+    Hash,
+    /// `--` line comments, as in SQL, Haskell and Lua.
+    Dashes,
+    /// `/* */` block comments, as in C and CSS.
+    CBlock,
+    /// `;;` line comments, as in Lisp and assembly.
+    Semicolons,
+    /// `<!-- -->` block comments, as in HTML and XML.
+    Html,
+}
+
+impl CommentStyle {
+
+    /// Get the comment prefix and suffix added to each row, including the blank that
+    /// separates them from the row.
+    pub fn delimiters(self) -> (&'static str, &'static str) {
+        match self {
+            CommentStyle::Slashes    => ("// ", ""),
+            CommentStyle::Hash       => ("# ", ""),
+            CommentStyle::Dashes     => ("-- ", ""),
+            CommentStyle::CBlock     => ("/* ", " */"),
+            CommentStyle::Semicolons => (";; ", ""),
+            CommentStyle::Html       => ("<!-- ", " -->"),
+        }
+    }
+
+    /// Get the number of columns taken by the comment delimiters.
+    pub fn width(self) -> usize {
+        let (prefix, suffix) = self.delimiters();
+        prefix.len() + suffix.len()
+    }
+
+    /// Replace sequences that would end the comment early with sequences of the same
+    /// width.
+    pub fn escape(self, row: &str) -> String {
+        match self {
+            CommentStyle::CBlock => row.replace("/*", "/+").replace("*/", "+/"),
+            CommentStyle::Html   => {
+                // every dash following another one is replaced, so runs of dashes
+                // are escaped too
+                let mut prev = ' ';
+                row.chars().map(|c| {
+                    let x = if c == '-' && prev == '-' { '~' } else { c };
+                    prev = c;
+                    x
+                }).collect()
+            },
+            _                    => row.to_owned(),
+        }
+    }
+
+    /// Wrap a row in the comment delimiters. Rows of line comments are not padded, and
+    /// empty rows get no trailing blank.
+    pub fn apply(self, row: &str) -> String {
+        let (prefix, suffix) = self.delimiters();
+        if row.is_empty() && suffix.is_empty() {
+            return prefix.trim_end().to_owned();
+        }
+        format!("{}{}{}", prefix, self.escape(row), suffix)
+    }
+}
+
+impl FromStr for CommentStyle {
+    type Err = Error;
+
+    /// Parse a comment style from its name or its opening delimiter.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "slashes"    | "//"   => Ok(CommentStyle::Slashes),
+            "hash"       | "#"    => Ok(CommentStyle::Hash),
+            "dashes"     | "--"   => Ok(CommentStyle::Dashes),
+            "c"          | "/*"   => Ok(CommentStyle::CBlock),
+            "semicolons" | ";;"   => Ok(CommentStyle::Semicolons),
+            "html"       | "<!--" => Ok(CommentStyle::Html),
+            _                     => Err(Error::CommentStyle(s.to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("#".parse::<CommentStyle>().unwrap(), CommentStyle::Hash);
+        assert_eq!("HTML".parse::<CommentStyle>().unwrap(), CommentStyle::Html);
+        assert_eq!("/*".parse::<CommentStyle>().unwrap(), CommentStyle::CBlock);
+        assert!(matches!("rem".parse::<CommentStyle>(), Err(Error::CommentStyle(_))));
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(CommentStyle::Slashes.apply(" _ "), "//  _ ");
+        assert_eq!(CommentStyle::Slashes.apply(""), "//");
+        assert_eq!(CommentStyle::CBlock.apply(""), "/*  */");
+        assert_eq!(CommentStyle::Html.apply("ab"), "<!-- ab -->");
+        assert_eq!(CommentStyle::Html.width(), 9);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(CommentStyle::CBlock.escape("/ __/ /*/"), "/ __/ /+/");
+        assert_eq!(CommentStyle::CBlock.escape("*/ /*"), "+/ /+");
+        assert_eq!(CommentStyle::Html.escape("--->"), "-~~>");
+        assert_eq!(CommentStyle::Hash.escape("*/--"), "*/--");
+    }
+}
@@ -1,10 +1,15 @@
+use std::borrow::Cow;
 use std::char;
 use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read, Seek, Write};
 use std::path::Path;
-use Error;
+use cell;
+use {Error, LayoutMode};
+#[cfg(feature = "serde")] use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "serde")] use serde::de;
+#[cfg(feature = "serde")] use std::convert::TryFrom;
 
 pub const SMUSH_EQUAL    : u32 = 1;
 pub const SMUSH_UNDERLINE: u32 = 2;
@@ -15,6 +20,20 @@ pub const SMUSH_HARDBLANK: u32 = 32;
 pub const SMUSH_KERN     : u32 = 64;
 pub const SMUSH_ENABLE   : u32 = 128;
 
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const COMPILED_MAGIC: &[u8] = b"RLF\x01";
+
+/// The file format of a FIGfont.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FontFormat {
+    /// A FIGlet font (.flf), with ASCII sub-characters.
+    #[default]
+    Flf,
+    /// A TOIlet font (.tlf), with UTF-8 Unicode sub-characters.
+    Tlf,
+}
+
 /// A font made of large ASCII-art characters.
 ///
 /// FIGfont defines a set of large ASCII-art (or UTF-8 Unicode art) characters,
@@ -22,8 +41,15 @@ pub const SMUSH_ENABLE   : u32 = 128;
 /// fit together in a line. All FIGcharacters in a font must have the same number
 /// of lines, and all lines in a FIGcharacter must have the same number of
 /// characters.
+///
+/// With the `serde` feature, fonts can be serialized and deserialized, so a parsed
+/// font can be cached and loaded again without parsing the font file. FIGcharacters
+/// are serialized as lists of lines.
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "FontData"))]
 pub struct FIGfont {
+    pub format    : FontFormat,
     version       : char,     // font standard version (currently 'a')
     pub hardblank : char,     // sub-character used to represent hardblanks
     pub height    : usize,
@@ -35,6 +61,8 @@ pub struct FIGfont {
     pub layout    : u32,
     count         : u32,      // number of code-tagged FIGcharacters in this FIGfont
     chars         : HashMap<char, FIGchar>, // actual FIGcharacter definitions for this font
+    #[cfg_attr(feature = "serde", serde(skip))]
+    clusters      : cell::Clusters,  // placeholders used to lay out FIGcharacters in cells
 }
 
 impl FIGfont {
@@ -45,13 +73,82 @@ impl FIGfont {
         font
     }
 
-    /// Create a new FIGfont from the specified .flf or .tlf file.
+    /// Create a new FIGfont from the specified .flf or .tlf file. Zipped fonts, as
+    /// distributed with FIGlet, are also accepted if the `zip` feature is enabled, as
+    /// well as fonts compiled with `compile`.
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
         let mut font = Self::new();
         try!(font.load(path));
         Ok(font)
     }
 
+    /// Create a new FIGfont from a compiled font, borrowing the FIGcharacter lines from
+    /// the data instead of copying them. Loading is not zero-copy: the character map is
+    /// built as for fonts parsed from text, with a vector of borrowed lines for each
+    /// FIGcharacter, and lines with grapheme clusters are copied when laid out in cells.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), rustlet::Error> {
+    /// // Usually the font is compiled with `rustlet compile` and embedded in the
+    /// // program with include_bytes!
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let data: &'static [u8] = Box::leak(font.compile().into_boxed_slice());
+    ///
+    /// let font = rustlet::FIGfont::from_bytes(data)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the data is not a valid compiled font, a FontFormat error is returned.
+    pub fn from_bytes(data: &'static [u8]) -> Result<Self, Error> {
+        let mut font = Self::new();
+        font.read_compiled(data, Cow::Borrowed)?;
+        Ok(font)
+    }
+
+    /// Compile this font to a compact binary format that can be loaded without parsing
+    /// FIGcharacter definitions.
+    ///
+    /// The compiled font has a header with the font parameters, followed by the sorted
+    /// list of FIGcharacter codes, a table with the offsets of each FIGcharacter line,
+    /// and the lines of all FIGcharacters stored in a single string. All numbers are
+    /// 32-bit little-endian values. Codes are sorted so compiling a font always gives
+    /// the same output; loaders read the tables in order and don't search them.
+    pub fn compile(&self) -> Vec<u8> {
+        let mut codes: Vec<&char> = self.chars.keys().collect();
+        codes.sort();
+
+        let mut offsets = vec![0];
+        let mut arena = String::new();
+        for c in &codes {
+            for line in &self.chars[c].lines {
+                arena.push_str(line);
+                offsets.push(arena.len() as u32);
+            }
+        }
+
+        let header = [
+            self.version as u32, self.hardblank as u32, self.height as u32,
+            self.baseline as u32, self.max_length as u32, self.old_layout as u32,
+            self.right_to_left as u32, self.layout, self.count, codes.len() as u32,
+        ];
+
+        let mut data = COMPILED_MAGIC.to_vec();
+        data.push(match self.format { FontFormat::Flf => 0, FontFormat::Tlf => 1 });
+        let words = header.iter().cloned()
+            .chain(codes.iter().map(|&&c| c as u32))
+            .chain(offsets);
+        for x in words {
+            let _ = data.write_all(&x.to_le_bytes());
+        }
+        data.extend_from_slice(arena.as_bytes());
+        data
+    }
+
     /// Obtain the FIGchar in this font for the given char.
     pub fn get(&self, ch: char) -> &FIGchar {
         match self.chars.get(&ch) {
@@ -62,9 +159,74 @@ impl FIGfont {
 
     /// Load a font from the given .flf or .tlf file.
     fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<&Self, Error> {
-        let file = try!(File::open(path));
-        let mut f = BufReader::new(&file);
+        let mut f = BufReader::new(File::open(path)?);
+        if f.fill_buf()?.starts_with(ZIP_MAGIC) {
+            let data = unzip(f)?;
+            return self.read(&mut &data[..]);
+        }
+        if f.fill_buf()?.starts_with(COMPILED_MAGIC) {
+            let mut data = Vec::new();
+            f.read_to_end(&mut data)?;
+            return self.read_compiled(&data, |x| Cow::Owned(x.to_owned()));
+        }
+        self.read(&mut f)
+    }
+
+    // Load a compiled font. FIGcharacter lines are converted by the given function, so
+    // they can be borrowed from static data. The code table is only read to build the
+    // same character map as fonts parsed from text.
+    fn read_compiled<'a, F>(&mut self, data: &'a [u8], line: F) -> Result<&Self, Error>
+        where F: Fn(&'a str) -> Cow<'static, str> {
+
+        if !data.starts_with(COMPILED_MAGIC) || data.len() < COMPILED_MAGIC.len() + 1 {
+            return Err(Error::FontFormat("invalid compiled font"));
+        }
 
+        let format = data[COMPILED_MAGIC.len()];
+        let mut words = &data[COMPILED_MAGIC.len() + 1..];
+        let mut next = || read_u32(&mut words);
+
+        self.format        = if format == 1 { FontFormat::Tlf } else { FontFormat::Flf };
+        self.version       = char_from_u32(next()?)?;
+        self.hardblank     = char_from_u32(next()?)?;
+        self.height        = next()? as usize;
+        self.baseline      = next()? as usize;
+        self.max_length    = next()? as usize;
+        self.old_layout    = next()? as i32;
+        self.right_to_left = next()? != 0;
+        self.layout        = next()?;
+        self.count         = next()?;
+
+        let num = next()? as usize;
+        let mut codes = Vec::new();
+        for _ in 0..num {
+            codes.push(char_from_u32(next()?)?);
+        }
+        let lines = num.checked_mul(self.height).and_then(|x| x.checked_add(1))
+            .ok_or(Error::FontFormat("invalid compiled font"))?;
+        let mut offsets = Vec::new();
+        for _ in 0..lines {
+            offsets.push(next()? as usize);
+        }
+
+        let arena = ::std::str::from_utf8(words).map_err(|_| Error::FontFormat("invalid compiled font"))?;
+        for (i, code) in codes.into_iter().enumerate() {
+            let mut c = FIGchar::new();
+            for j in i * self.height..(i + 1) * self.height {
+                let s = arena.get(offsets[j]..offsets[j + 1]).ok_or(Error::FontFormat("invalid compiled font"))?;
+                c.lines.push(line(s));
+            }
+            self.chars.insert(code, c);
+        }
+
+        if !self.chars.contains_key(&'\0') {
+            return Err(Error::FontFormat("invalid compiled font"));
+        }
+        self.layout_cells()?;
+        Ok(self)
+    }
+
+    fn read<R: BufRead>(&mut self, f: &mut R) -> Result<&Self, Error> {
         let mut line = String::new();
 
         try!(f.read_line(&mut line));
@@ -82,7 +244,7 @@ impl FIGfont {
         // Load required characters
         for i in (32..127).chain(vec![196, 215, 220, 228, 246, 252, 223]) {
             let mut c = FIGchar::new();
-            try!(c.load(&mut f, self.height));
+            try!(c.load(f, self.height));
             self.chars.insert(char_from_u32(i).unwrap(), c);
         }
 
@@ -98,13 +260,28 @@ impl FIGfont {
             };
 
             let mut c = FIGchar::new();
-            try!(c.load(&mut f, self.height));
+            try!(c.load(f, self.height));
             self.chars.insert(char_from_u32(u32_from_str(code)?)?, c);
         }
 
+        self.layout_cells()?;
         Ok(self)
     }
 
+    // Lay out all FIGcharacters in cells, using the placeholder table of this font.
+    fn layout_cells(&mut self) -> Result<(), Error> {
+        let table = &mut self.clusters;
+        for c in self.chars.values_mut() {
+            c.layout_cells(table)?;
+        }
+        Ok(())
+    }
+
+    // Get the placeholder table used to lay out the FIGcharacters of this font.
+    pub(crate) fn clusters(&self) -> &cell::Clusters {
+        &self.clusters
+    }
+
     fn parse_header(&mut self, line: &String) -> Result<&Self, Error> {
 
         if !line.starts_with("flf2") && !line.starts_with("tlf2") {
@@ -117,6 +294,12 @@ impl FIGfont {
             return Err(Error::FontFormat("unsupported font format"));
         }
 
+        // Print_Direction, Full_Layout and Codetag_Count are optional
+        if parms.len() < 6 {
+            return Err(Error::FontFormat("missing header parameters"));
+        }
+
+        self.format        = if line.starts_with("tlf2") { FontFormat::Tlf } else { FontFormat::Flf };
         self.version       = parms[0].chars().nth(4).unwrap();
         self.hardblank     = parms[0].chars().nth(5).unwrap();
         self.height        = try!(parms[1].parse());
@@ -124,14 +307,45 @@ impl FIGfont {
         self.max_length    = try!(parms[3].parse());
         self.old_layout    = try!(parms[4].parse());
         self.comment_lines = try!(parms[5].parse());
-        self.right_to_left = parms[6] == "1";
-        self.layout        = try!(parms[7].parse());
-        self.count         = try!(parms[8].parse());
+        self.right_to_left = parms.get(6) == Some(&"1");
+        self.layout        = match parms.get(7) {
+            Some(val) => val.parse()?,
+            None      => LayoutMode::from_old_layout(self.old_layout)?.to_full_layout(),
+        };
+        self.count         = match parms.get(8) {
+            Some(val) => val.parse()?,
+            None      => 0,
+        };
 
         Ok(self)
     }
 }
 
+// Extract the font from the first file in a zip archive.
+#[cfg(feature = "zip")]
+fn unzip<R: Read + Seek>(r: R) -> Result<Vec<u8>, Error> {
+    let mut archive = zip::ZipArchive::new(r).map_err(|_| Error::FontFormat("invalid zip archive"))?;
+    let mut file = archive.by_index(0).map_err(|_| Error::FontFormat("invalid zip archive"))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(not(feature = "zip"))]
+fn unzip<R: Read + Seek>(_: R) -> Result<Vec<u8>, Error> {
+    Err(Error::FontFormat("zipped fonts are not supported"))
+}
+
+// Read a little-endian 32-bit value from compiled font data.
+fn read_u32(data: &mut &[u8]) -> Result<u32, Error> {
+    if data.len() < 4 {
+        return Err(Error::FontFormat("invalid compiled font"));
+    }
+    let (x, rest) = data.split_at(4);
+    *data = rest;
+    Ok(u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+}
+
 fn char_from_u32(num: u32) -> Result<char, Error> {
     match char::from_u32(num) {
         Some(c) => Ok(c),
@@ -160,18 +374,20 @@ fn u32_from_str(s: &str) -> Result<u32, Error> {
 
 #[derive(Debug)]
 pub struct FIGchar {
-    lines: Vec<String>,
+    lines: Vec<Cow<'static, str>>,
+    cells: Option<Vec<Cow<'static, str>>>,   // lines laid out in terminal cells, if different
 }
 
 impl FIGchar {
     fn new() -> Self {
         FIGchar{
             lines: Vec::new(),
+            cells: None,
         }
     }
 
     /// Create a new FIGchar using the given set of lines. All lines must be valid UTF-8 strings
-    /// and have the same length in characters.
+    /// and have the same display width.
     ///
     /// # Example
     ///
@@ -188,18 +404,22 @@ impl FIGchar {
     pub fn from_lines(lines: &Vec<&str>) -> Result<Self, Error> {
         let mut c = Self::new();
         if !lines.is_empty() {
-            let width = lines[0].chars().count();
+            let width = cell::str_width(lines[0]);
             for line in lines {
-                if line.chars().count() != width {
+                if cell::str_width(line) != width {
                     return Err(Error::FontFormat("invalid character width"));
                 }
-                c.lines.push(line.to_string());
+                c.lines.push(Cow::Owned(line.to_string()));
             }
         }
+        // a FIGchar not loaded from a font has its own placeholders, which are laid out
+        // again with the font table if the FIGchar is added to a font
+        c.layout_cells(&mut cell::Clusters::default())?;
         Ok(c)
     }
 
-    /// Retrieve the lines from this FIGchar.
+    /// Retrieve a copy of the lines from this FIGchar. Use `lines` to access the lines
+    /// without copying them.
     ///
     /// # Example
     ///
@@ -214,12 +434,55 @@ impl FIGchar {
     /// # foo();
     /// ```
     pub fn get(&self) -> Vec<String> {
-        self.lines.to_owned()
+        self.lines.iter().map(|x| x.to_string()).collect()
+    }
+
+    /// Borrow the lines from this FIGchar, without copying them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), rustlet::Error> {
+    /// let c = rustlet::FIGchar::from_lines(&vec!["123", "456", "789"])?;
+    ///
+    /// assert_eq!(c.lines().collect::<Vec<_>>(), vec!["123", "456", "789"]);
+    /// assert_eq!((c.width(), c.height()), (3, 3));
+    /// # Ok(())
+    /// # }
+    /// # foo();
+    /// ```
+    pub fn lines(&self) -> impl ExactSizeIterator<Item=&str> {
+        self.lines.iter().map(|x| x.as_ref())
+    }
+
+    /// Get the width of this FIGchar in terminal columns.
+    pub fn width(&self) -> usize {
+        self.lines.first().map_or(0, |x| cell::str_width(x))
+    }
+
+    /// Get the number of lines in this FIGchar.
+    pub fn height(&self) -> usize {
+        self.lines.len()
+    }
+
+    // Borrow the lines from this FIGchar laid out in terminal cells, without copying.
+    pub(crate) fn cells(&self) -> &[Cow<'static, str>] {
+        self.cells.as_ref().unwrap_or(&self.lines)
+    }
+
+    fn layout_cells(&mut self, table: &mut cell::Clusters) -> Result<(), Error> {
+        let cells = self.lines.iter().map(|x| cell::to_cells(x, table)).collect::<Result<Vec<_>, _>>()?;
+        self.cells = match cells.iter().any(|x| x.is_some()) {
+            true  => Some(cells.into_iter().zip(&self.lines)
+                          .map(|(c, l)| c.map_or_else(|| l.clone(), Cow::Owned)).collect()),
+            false => None,
+        };
+        Ok(())
     }
 
     fn with_lines(num: usize) -> Self {
         let mut c = Self::new();
-        (0..num).for_each(|_| c.lines.push("".to_owned()));
+        (0..num).for_each(|_| c.lines.push(Cow::Borrowed("")));
         c
     }
 
@@ -232,7 +495,7 @@ impl FIGchar {
                 (i+1..height).for_each(|_| { let _ = f.read_line(&mut line); });
                 // If one line fails to load, clear other lines as well
                 self.lines.clear();
-                (0..height).for_each(|_| self.lines.push("".to_owned()));
+                (0..height).for_each(|_| self.lines.push(Cow::Borrowed("")));
                 return Ok(self)
             }
             line = line.trim_right().to_string();
@@ -240,13 +503,84 @@ impl FIGchar {
                 return Err(Error::FontFormat("invalid character width"));
             }
             let mark = line.pop().unwrap();
-            self.lines.push(line.trim_right_matches(mark).to_string());
+            self.lines.push(Cow::Owned(line.trim_right_matches(mark).to_string()));
         }
 
         Ok(self)
     }
 }
 
+// A deserialized FIGfont, before its FIGcharacters are laid out in cells. Placeholders
+// are not serialized, so cells are laid out again using a new table for the font.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct FontData {
+    format       : FontFormat,
+    version      : char,
+    hardblank    : char,
+    height       : usize,
+    baseline     : usize,
+    max_length   : usize,
+    old_layout   : i32,
+    comment_lines: usize,
+    right_to_left: bool,
+    layout       : u32,
+    count        : u32,
+    chars        : HashMap<char, FIGchar>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<FontData> for FIGfont {
+    type Error = Error;
+
+    fn try_from(data: FontData) -> Result<Self, Error> {
+        // missing characters are replaced with the '\0' glyph, so it must exist
+        if !data.chars.contains_key(&'\0') {
+            return Err(Error::FontFormat("missing default FIGcharacter"));
+        }
+        if data.chars.values().any(|x| x.lines.len() != data.height) {
+            return Err(Error::FontFormat("inconsistent FIGcharacter height"));
+        }
+
+        let mut font = FIGfont{
+            format       : data.format,
+            version      : data.version,
+            hardblank    : data.hardblank,
+            height       : data.height,
+            baseline     : data.baseline,
+            max_length   : data.max_length,
+            old_layout   : data.old_layout,
+            comment_lines: data.comment_lines,
+            right_to_left: data.right_to_left,
+            layout       : data.layout,
+            count        : data.count,
+            chars        : data.chars,
+            clusters     : Default::default(),
+        };
+        font.layout_cells()?;
+        Ok(font)
+    }
+}
+
+// Only the lines are stored, cells are laid out again when the FIGchar is loaded.
+#[cfg(feature = "serde")]
+impl Serialize for FIGchar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.lines.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for FIGchar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // lines may have different widths, as in FIGcharacters loaded from font files
+        let mut c = FIGchar::new();
+        c.lines = Vec::<String>::deserialize(deserializer)?.into_iter().map(Cow::Owned).collect();
+        c.layout_cells(&mut cell::Clusters::default()).map_err(de::Error::custom)?;
+        Ok(c)
+    }
+}
+
 impl fmt::Display for FIGchar {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut s = String::new();
@@ -261,6 +595,14 @@ impl fmt::Display for FIGchar {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::{env, fs, process};
+    use Smusher;
+
+    fn render(font: &FIGfont, s: &str) -> Vec<String> {
+        let mut sm = Smusher::new(font);
+        sm.push_str(s);
+        sm.get()
+    }
 
     #[test]
     fn test_display() {
@@ -324,6 +666,34 @@ mod tests {
                                             r"         "]);
     }
 
+    #[test]
+    fn test_parse_header() {
+        let mut font = FIGfont::new();
+        font.parse_header(&"flf2a$ 6 5 16 15 11 0 24463 229".to_owned()).unwrap();
+        assert_eq!((font.hardblank, font.height, font.old_layout), ('$', 6, 15));
+        assert_eq!((font.layout, font.count, font.right_to_left), (24463, 229, false));
+        assert_eq!(font.format, FontFormat::Flf);
+
+        // Full_Layout is derived from Old_Layout when absent
+        let layouts = [(-1, 0), (0, 64), (15, 143), (63, 191)];
+        for &(old, full) in layouts.iter() {
+            let mut font = FIGfont::new();
+            font.parse_header(&format!("flf2a$ 4 3 8 {} 2", old)).unwrap();
+            assert_eq!((font.old_layout, font.layout, font.count), (old, full, 0));
+        }
+
+        let mut font = FIGfont::new();
+        font.parse_header(&"tlf2a# 4 3 8 0 2 1".to_owned()).unwrap();
+        assert_eq!((font.hardblank, font.layout, font.right_to_left), ('#', 64, true));
+        assert_eq!(font.format, FontFormat::Tlf);
+
+        let mut font = FIGfont::new();
+        assert!(matches!(font.parse_header(&"flf2a$ 4 3 8 64 2".to_owned()),
+                         Err(Error::Layout(64))));
+        assert!(matches!(font.parse_header(&"flf2a$ 4 3 8".to_owned()),
+                         Err(Error::FontFormat(_))));
+    }
+
     #[test]
     fn test_get_tab() {
         let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/standard.flf";
@@ -346,4 +716,118 @@ mod tests {
         let c = FIGchar::from_lines(&vec!["123", "456", "7890"]);
         assert!(matches!(c, Err(Error::FontFormat(_))));
     }
+
+    #[test]
+    fn test_char_lines() {
+        let c = FIGchar::from_lines(&vec!["全e\u{301}", "$ab"]).unwrap();
+        assert_eq!(c.lines().collect::<Vec<_>>(), vec!["全e\u{301}", "$ab"]);
+        assert_eq!(c.lines().len(), 2);
+        assert_eq!((c.width(), c.height()), (3, 2));
+
+        let c = FIGchar::with_lines(0);
+        assert_eq!((c.width(), c.height()), (0, 0));
+    }
+
+    #[test]
+    fn test_compile() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/wide.tlf";
+        let font = FIGfont::from_path(&path).unwrap();
+        let data: &'static [u8] = Box::leak(font.compile().into_boxed_slice());
+        let copy = FIGfont::from_bytes(data).unwrap();
+        assert_eq!(copy.format, FontFormat::Tlf);
+        assert_eq!((copy.hardblank, copy.height, copy.layout), (font.hardblank, font.height, font.layout));
+        assert_eq!(copy.chars.len(), font.chars.len());
+        for (ch, c) in &font.chars {
+            assert_eq!(copy.get(*ch).get(), c.get());
+        }
+        assert_eq!(render(&copy, "AB*e\u{e000}"), render(&font, "AB*e\u{e000}"));
+        assert!(copy.get('A').lines.iter().all(|x| matches!(*x, Cow::Borrowed(_))));
+
+        let path = env::temp_dir().join(format!("rustlet-test-{}.rlf", process::id()));
+        fs::write(&path, data).unwrap();
+        let loaded = FIGfont::from_path(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap().get('B').get(), font.get('B').get());
+    }
+
+    #[test]
+    fn test_compiled_invalid() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/small.flf";
+        let data = FIGfont::from_path(&path).unwrap().compile();
+        for len in [0, 5, 40, data.len() - 1].iter() {
+            let data: &'static [u8] = Box::leak(data[..*len].to_vec().into_boxed_slice());
+            assert!(matches!(FIGfont::from_bytes(data), Err(Error::FontFormat(_))));
+        }
+
+        // huge counts in the header are rejected, not allocated or overflowed
+        let mut data = data;
+        let height = COMPILED_MAGIC.len() + 1 + 2 * 4;
+        let num = COMPILED_MAGIC.len() + 1 + 9 * 4;
+        data[height..height + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        data[num..num + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let data: &'static [u8] = Box::leak(data.into_boxed_slice());
+        assert!(FIGfont::from_bytes(data).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/wide.tlf";
+        let font = FIGfont::from_path(&path).unwrap();
+        let data = serde_json::to_string(&font).unwrap();
+        let copy: FIGfont = serde_json::from_str(&data).unwrap();
+        assert_eq!(copy.format, FontFormat::Tlf);
+        assert_eq!(copy.height, font.height);
+        assert_eq!(copy.layout, font.layout);
+        assert_eq!(copy.chars.len(), font.chars.len());
+        for c in "AB*e".chars() {
+            assert_eq!(copy.get(c).get(), font.get(c).get());
+        }
+        assert_eq!(render(&copy, "AB*e\u{e000}"), render(&font, "AB*e\u{e000}"));
+
+        let c: FIGchar = serde_json::from_str(r#"["ab", "cd"]"#).unwrap();
+        assert_eq!(c.get(), vec!["ab", "cd"]);
+        let c: FIGchar = serde_json::from_str(r#"["ab", "c"]"#).unwrap();
+        assert_eq!(c.get(), vec!["ab", "c"]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_fonts() {
+        let dir = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts";
+        for path in fs::read_dir(dir).unwrap().map(|x| x.unwrap().path()) {
+            let font = FIGfont::from_path(&path).unwrap();
+            let data = serde_json::to_string(&font).unwrap();
+            let copy: FIGfont = serde_json::from_str(&data).unwrap();
+            assert_eq!(copy.chars.len(), font.chars.len(), "{:?}", path);
+            for (code, c) in &font.chars {
+                assert_eq!(copy.get(*code).get(), c.get(), "{:?} {:?}", path, code);
+            }
+        }
+
+        // font files don't require all lines of a FIGcharacter to have the same width
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/small.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+        let mut data = serde_json::to_value(&font).unwrap();
+        data["chars"][" "][0] = serde_json::json!("$$$$");
+        let copy: FIGfont = serde_json::from_value(data).unwrap();
+        assert_eq!(copy.get(' ').get()[0], "$$$$");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_invalid() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+        let mut data: serde_json::Value = serde_json::to_value(&font).unwrap();
+
+        let mut empty = data.clone();
+        empty["chars"] = serde_json::json!({});
+        let err = serde_json::from_value::<FIGfont>(empty).unwrap_err();
+        assert!(err.to_string().contains("missing default FIGcharacter"));
+
+        data["height"] = serde_json::json!(font.height + 1);
+        let err = serde_json::from_value::<FIGfont>(data).unwrap_err();
+        assert!(err.to_string().contains("inconsistent FIGcharacter height"));
+    }
 }
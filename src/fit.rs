@@ -0,0 +1,196 @@
+use figfont::{FIGfont, SMUSH_ENABLE};
+use wrapper;
+use {measure, LayoutMode, Smusher, Wrapper};
+
+/// Text rendered to fit a target size.
+///
+/// Fit holds the output of `fit`, along with the font and layout mode chosen to
+/// render the text.
+#[derive(Debug)]
+pub struct Fit {
+    pub font      : usize,        // index of the chosen font in the list of candidates
+    pub mode      : LayoutMode,   // the layout mode used to render the text
+    pub wrapped   : bool,         // whether the text had to be wrapped to fit
+    pub lines     : Vec<String>,  // the rendered text
+}
+
+/// Render text using the largest font and layout mode that fit the given size.
+///
+/// Candidate fonts are tried from the tallest to the shortest, skipping fonts whose
+/// rendering is taller than the maximum height (if any), counting every line of text.
+/// For each font, full-width, kerning and smushing layouts are tried in this order, and
+/// the first rendering that fits in the given width without wrapping is returned. If no font fits, the text is wrapped using the
+/// tallest font whose output fits the maximum height, or the shortest font if none does.
+/// Returns None if there are no candidate fonts.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let standard = rustlet::FIGfont::from_path("standard.flf")?;
+/// let small = rustlet::FIGfont::from_path("small.flf")?;
+///
+/// // Render text to fit in a 40-column panel
+/// if let Some(fit) = rustlet::fit("Hello world", 40, None, &[&standard, &small]) {
+///     fit.lines.iter().for_each(|x| println!("{}", x));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn fit(text: &str, width: usize, height: Option<usize>, fonts: &[&FIGfont]) -> Option<Fit> {
+
+    // sort candidates from the tallest to the shortest font
+    let mut order: Vec<usize> = (0..fonts.len()).collect();
+    order.sort_by(|a, b| fonts[*b].height.cmp(&fonts[*a].height));
+
+    let max_height = height.unwrap_or(usize::MAX);
+
+    for &i in order.iter().filter(|&&i| fonts[i].height <= max_height) {
+        let font = fonts[i];
+        for &mode in layouts(font).iter() {
+            let (w, h) = measure(font, text, mode);
+            if w <= width && h <= max_height {
+                let mut sm = Smusher::new(font);
+                sm.mode = mode;
+                sm.push_str(text);
+                return Some(Fit{
+                    font   : i,
+                    mode,
+                    wrapped: false,
+                    lines  : sm.get(),
+                })
+            }
+        }
+    }
+
+    // nothing fits in a single line, wrap text using the narrowest layout, starting from
+    // the shortest font
+    let mut best = None;
+    for &i in order.iter().rev() {
+        let font = fonts[i];
+        let mode = smushing(font);
+        let lines = wrap(font, text, width, mode);
+        if lines.len() > max_height && best.is_some() {
+            continue;
+        }
+        best = Some(Fit{
+            font   : i,
+            mode,
+            wrapped: true,
+            lines,
+        });
+    }
+
+    best
+}
+
+// Layout modes to try, from the widest to the narrowest.
+fn layouts(font: &FIGfont) -> [LayoutMode; 3] {
+    [
+        LayoutMode::FullWidth,
+        LayoutMode::Fitting,
+        smushing(font),
+    ]
+}
+
+// The narrowest layout mode: the font smushing rules, or universal smushing if the font
+// has no rules.
+fn smushing(font: &FIGfont) -> LayoutMode {
+    LayoutMode::from_full_layout(font.layout | SMUSH_ENABLE)
+}
+
+fn wrap(font: &FIGfont, text: &str, width: usize, mode: LayoutMode) -> Vec<String> {
+    let mut sm = Smusher::new(font);
+    sm.mode = mode;
+    let mut wr = Wrapper::new(sm, width);
+
+    let mut lines = Vec::new();
+    for token in wrapper::tokens(text) {
+        let _ = wr.try_wrap_str(token, &mut |v| { lines.extend_from_slice(v); Ok(()) });
+    }
+    lines.extend(wr.get());
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str) -> FIGfont {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/" + name;
+        FIGfont::from_path(&path).unwrap()
+    }
+
+    #[test]
+    fn test_fit_layout() {
+        let standard = load("standard.flf");
+        let small = load("small.flf");
+        let fonts = [&small, &standard];
+
+        let text = "Hello";
+        let smushing = LayoutMode::from_full_layout(standard.layout);
+        let full = measure(&standard, text, LayoutMode::FullWidth).0;
+        let kern = measure(&standard, text, LayoutMode::Fitting).0;
+        let smush = measure(&standard, text, smushing).0;
+        assert!(smush < kern && kern < full);
+
+        let f = fit(text, full, None, &fonts).unwrap();
+        assert_eq!((f.font, f.mode, f.wrapped), (1, LayoutMode::FullWidth, false));
+        assert_eq!(f.lines[0].chars().count(), full);
+
+        let f = fit(text, full - 1, None, &fonts).unwrap();
+        assert_eq!((f.font, f.mode), (1, LayoutMode::Fitting));
+
+        let f = fit(text, smush, None, &fonts).unwrap();
+        assert_eq!((f.font, f.mode), (1, smushing));
+    }
+
+    #[test]
+    fn test_fit_font() {
+        let standard = load("standard.flf");
+        let small = load("small.flf");
+        let fonts = [&small, &standard];
+
+        let text = "Hello";
+        let smush = measure(&standard, text, LayoutMode::from_full_layout(standard.layout)).0;
+        let f = fit(text, smush - 1, None, &fonts).unwrap();
+        assert_eq!((f.font, f.wrapped), (0, false));
+        assert_eq!(f.lines.len(), small.height);
+
+        let f = fit(text, 200, Some(small.height), &fonts).unwrap();
+        assert_eq!((f.font, f.mode), (0, LayoutMode::FullWidth));
+    }
+
+    #[test]
+    fn test_fit_wrap() {
+        let standard = load("standard.flf");
+        let small = load("small.flf");
+        let fonts = [&small, &standard];
+
+        let f = fit("Hello world", 30, None, &fonts).unwrap();
+        assert_eq!((f.font, f.wrapped), (1, true));
+        assert_eq!(f.lines.len(), 2 * standard.height);
+
+        let f = fit("Hello world", 30, Some(10), &fonts).unwrap();
+        assert_eq!((f.font, f.wrapped), (0, true));
+        assert_eq!(f.lines.len(), 2 * small.height);
+
+        assert!(fit("Hello", 30, None, &[]).is_none());
+    }
+
+    #[test]
+    fn test_fit_lines() {
+        let standard = load("standard.flf");
+        let small = load("small.flf");
+        let fonts = [&standard, &small];
+
+        // all lines of text count towards the maximum height
+        let f = fit("a\nb\nc", 80, Some(3 * standard.height), &fonts).unwrap();
+        assert_eq!((f.font, f.wrapped), (0, false));
+        let f = fit("a\nb\nc", 80, Some(standard.height), &fonts).unwrap();
+        assert_eq!(f.font, 1);
+        let f = fit("a\nb\nc", 80, Some(3 * small.height), &fonts).unwrap();
+        assert_eq!((f.font, f.wrapped), (1, false));
+        assert_eq!(f.lines.len(), 3 * small.height);
+    }
+}
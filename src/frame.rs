@@ -0,0 +1,186 @@
+use std::str::FromStr;
+use cell;
+use Error;
+use Margins;
+#[cfg(feature = "serde")] use serde::{Deserialize, Serialize};
+
+/// Line style of a frame border.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FrameStyle {
+    /// ASCII characters: `+-|`.
+    #[default]
+    Ascii,
+    /// Single box-drawing lines: `┌─┐`.
+    Single,
+    /// Double box-drawing lines: `╔═╗`.
+    Double,
+    /// Single box-drawing lines with rounded corners: `╭─╮`.
+    Rounded,
+    /// Heavy box-drawing lines: `┏━┓`.
+    Heavy,
+}
+
+impl FrameStyle {
+    // Border characters: horizontal, vertical and the top left, top right, bottom left
+    // and bottom right corners.
+    fn chars(self) -> [char; 6] {
+        match self {
+            FrameStyle::Ascii   => ['-', '|', '+', '+', '+', '+'],
+            FrameStyle::Single  => ['─', '│', '┌', '┐', '└', '┘'],
+            FrameStyle::Double  => ['═', '║', '╔', '╗', '╚', '╝'],
+            FrameStyle::Rounded => ['─', '│', '╭', '╮', '╰', '╯'],
+            FrameStyle::Heavy   => ['━', '┃', '┏', '┓', '┗', '┛'],
+        }
+    }
+}
+
+impl FromStr for FrameStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "ascii"   => Ok(FrameStyle::Ascii),
+            "single"  => Ok(FrameStyle::Single),
+            "double"  => Ok(FrameStyle::Double),
+            "rounded" => Ok(FrameStyle::Rounded),
+            "heavy"   => Ok(FrameStyle::Heavy),
+            _         => Err(Error::FrameStyle(s.to_owned())),
+        }
+    }
+}
+
+/// A border drawn around the output rows.
+///
+/// Frames are set in a Wrapper and drawn by `Wrapper::finish` around the complete
+/// output, after the rows are aligned, so the frame always spans the whole text width.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let font = rustlet::FIGfont::from_path("small.flf")?;
+/// let mut wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 40);
+///
+/// let mut frame = rustlet::Frame::new(rustlet::FrameStyle::Rounded);
+/// frame.padding.left = 1;
+/// frame.title = Some("build".to_string());
+/// wr.frame = Some(frame);
+/// wr.align = rustlet::Align::Center;
+///
+/// wr.push_str("ok")?;
+/// let rows = wr.get();
+/// for line in wr.finish(&rows)? {
+///     println!("{}", line);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Frame {
+    pub style  : FrameStyle,        // border line style
+    pub padding: Margins,           // blank space between the border and the text
+    pub title  : Option<String>,    // text shown in the top border
+}
+
+impl Frame {
+
+    /// Create a new frame with the given style, no padding and no title.
+    pub fn new(style: FrameStyle) -> Self {
+        Frame{
+            style,
+            ..Default::default()
+        }
+    }
+
+    /// Get the number of columns taken by the border and the horizontal padding.
+    pub fn width(&self) -> usize {
+        2 + self.padding.left + self.padding.right
+    }
+
+    /// Draw the frame around rows of text, padding them to the given width in columns.
+    /// Rows wider than the width are not truncated.
+    pub fn draw(&self, rows: &[String], width: usize) -> Vec<String> {
+        let [h, v, tl, tr, bl, br] = self.style.chars();
+        let inner = width + self.padding.left + self.padding.right;
+
+        let mut top = String::new();
+        top.push(tl);
+        let mut n = 0;
+        if let Some(ref title) = self.title {
+            // leave room for a border char on each side of the title
+            if inner >= 4 {
+                let title = cell::truncate(&format!(" {} ", title), inner - 2);
+                top.push(h);
+                n = cell::str_width(&title) + 1;
+                top.push_str(&title);
+            }
+        }
+        top.extend((n..inner).map(|_| h));
+        top.push(tr);
+
+        let blank = |n: usize| (0..n).map(|_| ' ').collect::<String>();
+        let side = |row: &str| {
+            let pad = width - width.min(cell::str_width(row));
+            format!("{}{}{}{}{}{}", v, blank(self.padding.left), row, blank(pad),
+                    blank(self.padding.right), v)
+        };
+
+        let mut out = vec![top];
+        out.extend((0..self.padding.top).map(|_| side("")));
+        out.extend(rows.iter().map(|x| side(x)));
+        out.extend((0..self.padding.bottom).map(|_| side("")));
+
+        let mut bottom = String::new();
+        bottom.push(bl);
+        bottom.extend((0..inner).map(|_| h));
+        bottom.push(br);
+        out.push(bottom);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(v: &[&str]) -> Vec<String> {
+        v.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("ascii".parse::<FrameStyle>().unwrap(), FrameStyle::Ascii);
+        assert_eq!("Double".parse::<FrameStyle>().unwrap(), FrameStyle::Double);
+        assert!(matches!("dotted".parse::<FrameStyle>(), Err(Error::FrameStyle(_))));
+    }
+
+    #[test]
+    fn test_draw() {
+        let frame = Frame::new(FrameStyle::Ascii);
+        assert_eq!(frame.width(), 2);
+        assert_eq!(frame.draw(&rows(&["ab", "c"]), 3), vec!["+---+", "|ab |", "|c  |", "+---+"]);
+
+        let frame = Frame::new(FrameStyle::Rounded);
+        assert_eq!(frame.draw(&rows(&["全"]), 2), vec!["╭──╮", "│全│", "╰──╯"]);
+    }
+
+    #[test]
+    fn test_padding() {
+        let mut frame = Frame::new(FrameStyle::Single);
+        frame.padding = Margins{ top: 1, right: 1, bottom: 0, left: 2 };
+        assert_eq!(frame.width(), 5);
+        assert_eq!(frame.draw(&rows(&["ab"]), 2), vec!["┌─────┐", "│     │", "│  ab │", "└─────┘"]);
+    }
+
+    #[test]
+    fn test_title() {
+        let mut frame = Frame::new(FrameStyle::Ascii);
+        frame.title = Some("ci".to_string());
+        assert_eq!(frame.draw(&rows(&["abc"]), 8)[0], "+- ci ---+");
+        assert_eq!(frame.draw(&rows(&["abc"]), 5)[0], "+- ci-+");
+        assert_eq!(frame.draw(&rows(&["abc"]), 4)[0], "+- c-+");
+        assert_eq!(frame.draw(&rows(&["abc"]), 3)[0], "+---+");
+    }
+}
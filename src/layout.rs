@@ -0,0 +1,263 @@
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+use figfont::{SMUSH_EQUAL, SMUSH_UNDERLINE, SMUSH_HIERARCHY, SMUSH_PAIR, SMUSH_BIGX,
+              SMUSH_HARDBLANK, SMUSH_KERN, SMUSH_ENABLE};
+use Error;
+#[cfg(feature = "serde")] use serde::{Deserialize, Serialize};
+
+const RULES_MASK: u32 = SMUSH_KERN - 1;
+
+/// A set of horizontal smushing rules.
+///
+/// The rules are the six horizontal smushing rules described in the FIGfont
+/// specification, and can be combined using the `|` operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SmushRules(u32);
+
+impl SmushRules {
+    /// Rule 1: equal character smushing.
+    pub const EQUAL    : SmushRules = SmushRules(SMUSH_EQUAL);
+    /// Rule 2: underscore smushing.
+    pub const UNDERLINE: SmushRules = SmushRules(SMUSH_UNDERLINE);
+    /// Rule 3: hierarchy smushing.
+    pub const HIERARCHY: SmushRules = SmushRules(SMUSH_HIERARCHY);
+    /// Rule 4: opposite pair smushing.
+    pub const PAIR     : SmushRules = SmushRules(SMUSH_PAIR);
+    /// Rule 5: big X smushing.
+    pub const BIGX     : SmushRules = SmushRules(SMUSH_BIGX);
+    /// Rule 6: hardblank smushing.
+    pub const HARDBLANK: SmushRules = SmushRules(SMUSH_HARDBLANK);
+
+    /// Create an empty rule set.
+    pub const fn empty() -> Self {
+        SmushRules(0)
+    }
+
+    /// Create a rule set containing all six smushing rules.
+    pub const fn all() -> Self {
+        SmushRules(RULES_MASK)
+    }
+
+    /// Create a rule set from its code values, as used in the FIGfont header layout
+    /// parameters. Code values other than horizontal smushing rules are ignored.
+    pub const fn from_bits(bits: u32) -> Self {
+        SmushRules(bits & RULES_MASK)
+    }
+
+    /// Get the sum of the code values of the rules in this set.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Verify whether this rule set contains all rules in the other set.
+    pub fn contains(&self, other: SmushRules) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Verify whether this rule set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for SmushRules {
+    type Output = SmushRules;
+
+    fn bitor(self, other: SmushRules) -> SmushRules {
+        SmushRules(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for SmushRules {
+    fn bitor_assign(&mut self, other: SmushRules) {
+        self.0 |= other.0;
+    }
+}
+
+/// The horizontal layout mode, which controls how FIGcharacters are fit together in
+/// a line.
+///
+/// # Examples
+///
+/// ```
+/// use rustlet::{LayoutMode, SmushRules};
+///
+/// // Layout parameters from the standard.flf header
+/// let mode = LayoutMode::from_full_layout(24463);
+/// assert_eq!(mode, LayoutMode::Smushing{ rules: SmushRules::from_bits(15) });
+/// assert_eq!(mode.to_old_layout(), 15);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LayoutMode {
+    /// Each FIGcharacter occupies its full width.
+    FullWidth,
+    /// FIGcharacters are moved closer until they touch (kerning).
+    Fitting,
+    /// FIGcharacters are moved one step further, and the touching sub-characters are
+    /// smushed into one using the given rules. The rule set is never empty: as in the
+    /// FIGfont specification, smushing without rules is universal smushing. Use
+    /// `LayoutMode::smushing` to create this mode from a rule set that may be empty.
+    Smushing{ rules: SmushRules },
+    /// FIGcharacters are moved one step further, and sub-characters from the later
+    /// FIGcharacter override the earlier ones.
+    Universal,
+}
+
+impl LayoutMode {
+
+    /// Create a smushing layout mode using the given rules. An empty rule set results in
+    /// universal smushing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rustlet::{LayoutMode, SmushRules};
+    ///
+    /// assert_eq!(LayoutMode::smushing(SmushRules::empty()), LayoutMode::Universal);
+    /// ```
+    pub fn smushing(rules: SmushRules) -> Self {
+        match rules.is_empty() {
+            true  => LayoutMode::Universal,
+            false => LayoutMode::Smushing{ rules },
+        }
+    }
+
+    /// Convert the Old_Layout parameter from a FIGfont header to a layout mode.
+    ///
+    /// # Errors
+    ///
+    /// Legal values are -1 to 63. If the value is outside this range, a Layout error is
+    /// returned.
+    pub fn from_old_layout(old_layout: i32) -> Result<Self, Error> {
+        match old_layout {
+            -1     => Ok(LayoutMode::FullWidth),
+            0      => Ok(LayoutMode::Fitting),
+            1..=63 => Ok(LayoutMode::smushing(SmushRules::from_bits(old_layout as u32))),
+            _      => Err(Error::Layout(old_layout)),
+        }
+    }
+
+    /// Convert the Full_Layout parameter from a FIGfont header to a layout mode. Only the
+    /// horizontal layout is considered.
+    pub fn from_full_layout(full_layout: u32) -> Self {
+        if full_layout & SMUSH_ENABLE != 0 {
+            LayoutMode::smushing(SmushRules::from_bits(full_layout))
+        } else if full_layout & SMUSH_KERN != 0 {
+            LayoutMode::Fitting
+        } else {
+            LayoutMode::FullWidth
+        }
+    }
+
+    /// Convert this layout mode to the FIGfont header Old_Layout parameter. Universal
+    /// smushing can't be represented in Old_Layout and is converted to 0 (kerning), as
+    /// required by the specification.
+    pub fn to_old_layout(&self) -> i32 {
+        match *self {
+            LayoutMode::FullWidth         => -1,
+            LayoutMode::Fitting           => 0,
+            LayoutMode::Smushing{ rules } => rules.bits() as i32,
+            LayoutMode::Universal         => 0,
+        }
+    }
+
+    /// Convert this layout mode to the horizontal part of the FIGfont header Full_Layout
+    /// parameter.
+    pub fn to_full_layout(&self) -> u32 {
+        match *self {
+            LayoutMode::FullWidth         => 0,
+            LayoutMode::Fitting           => SMUSH_KERN,
+            LayoutMode::Smushing{ rules } => SMUSH_ENABLE | rules.bits(),
+            LayoutMode::Universal         => SMUSH_ENABLE,
+        }
+    }
+}
+
+impl fmt::Display for LayoutMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LayoutMode::FullWidth         => write!(f, "full width"),
+            LayoutMode::Fitting           => write!(f, "fitting"),
+            LayoutMode::Smushing{ rules } => write!(f, "smushing (rules {})", rules.bits()),
+            LayoutMode::Universal         => write!(f, "universal smushing"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let rules = SmushRules::EQUAL | SmushRules::BIGX;
+        assert_eq!(rules.bits(), 17);
+        assert!(rules.contains(SmushRules::EQUAL));
+        assert!(!rules.contains(SmushRules::PAIR));
+        assert!(SmushRules::all().contains(rules));
+        assert!(SmushRules::empty().is_empty());
+        assert_eq!(SmushRules::from_bits(24463).bits(), 15);
+    }
+
+    #[test]
+    fn test_from_old_layout() {
+        assert!(matches!(LayoutMode::from_old_layout(-1), Ok(LayoutMode::FullWidth)));
+        assert!(matches!(LayoutMode::from_old_layout(0), Ok(LayoutMode::Fitting)));
+        assert_eq!(LayoutMode::from_old_layout(15).unwrap(),
+                   LayoutMode::Smushing{ rules: SmushRules::from_bits(15) });
+        assert_eq!(LayoutMode::from_old_layout(63).unwrap(),
+                   LayoutMode::Smushing{ rules: SmushRules::all() });
+        assert!(matches!(LayoutMode::from_old_layout(-2), Err(Error::Layout(-2))));
+        assert!(matches!(LayoutMode::from_old_layout(64), Err(Error::Layout(64))));
+    }
+
+    #[test]
+    fn test_from_full_layout() {
+        assert_eq!(LayoutMode::from_full_layout(0), LayoutMode::FullWidth);
+        assert_eq!(LayoutMode::from_full_layout(15), LayoutMode::FullWidth);
+        assert_eq!(LayoutMode::from_full_layout(64), LayoutMode::Fitting);
+        assert_eq!(LayoutMode::from_full_layout(576), LayoutMode::Fitting);
+        assert_eq!(LayoutMode::from_full_layout(128), LayoutMode::Universal);
+        assert_eq!(LayoutMode::from_full_layout(1920), LayoutMode::Universal);
+        assert_eq!(LayoutMode::from_full_layout(24463),
+                   LayoutMode::Smushing{ rules: SmushRules::from_bits(15) });
+        assert_eq!(LayoutMode::from_full_layout(128 + 64 + 1),
+                   LayoutMode::Smushing{ rules: SmushRules::EQUAL });
+    }
+
+    #[test]
+    fn test_to_header() {
+        let modes = [ LayoutMode::FullWidth, LayoutMode::Fitting, LayoutMode::Universal,
+                      LayoutMode::Smushing{ rules: SmushRules::PAIR | SmushRules::HARDBLANK } ];
+        for mode in modes.iter() {
+            assert_eq!(LayoutMode::from_full_layout(mode.to_full_layout()), *mode);
+        }
+        assert_eq!(modes.iter().map(|x| x.to_old_layout()).collect::<Vec<_>>(), vec![-1, 0, 0, 40]);
+        assert_eq!(modes.iter().map(|x| x.to_full_layout()).collect::<Vec<_>>(),
+                   vec![0, 64, 128, 168]);
+    }
+
+    #[test]
+    fn test_smushing() {
+        assert_eq!(LayoutMode::smushing(SmushRules::empty()), LayoutMode::Universal);
+        assert_eq!(LayoutMode::smushing(SmushRules::BIGX), LayoutMode::Smushing{ rules: SmushRules::BIGX });
+
+        // every mode converts to a header parameter and back to the same mode
+        for old in -1..=63 {
+            let mode = LayoutMode::from_old_layout(old).unwrap();
+            assert_eq!(LayoutMode::from_full_layout(mode.to_full_layout()), mode);
+        }
+        let mode = LayoutMode::smushing(SmushRules::empty());
+        assert_eq!(LayoutMode::from_full_layout(mode.to_full_layout()), mode);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(LayoutMode::FullWidth.to_string(), "full width");
+        assert_eq!(LayoutMode::Fitting.to_string(), "fitting");
+        assert_eq!(LayoutMode::Universal.to_string(), "universal smushing");
+        assert_eq!(LayoutMode::from_full_layout(24463).to_string(), "smushing (rules 15)");
+    }
+}
@@ -1,18 +1,41 @@
 #[cfg(test)] #[macro_use] extern crate matches;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(all(test, feature = "serde"))] extern crate serde_json;
+#[cfg(feature = "zip")] extern crate zip;
+extern crate unicode_segmentation;
+extern crate unicode_width;
 
 use std::error;
 use std::fmt;
 use std::io;
 use std::num;
+use std::path::PathBuf;
 
 //pub use self::figfont::{FIGchar, FIGfont};
 pub use self::figfont::*;
-pub use self::wrapper::{Align, Wrapper};
-pub use self::smusher::Smusher;
+pub use self::wrapper::{Align, Margins, VAlign, Wrapper};
+pub use self::smusher::{measure, measure_with_rules, ControlChars, Smusher};
+pub use self::smusher::{RuleSet, SmushRule};
+pub use self::smusher::{BigXRule, EqualRule, HardblankRule, HierarchyRule, PairRule, UnderlineRule};
+pub use self::renderer::Renderer;
+pub use self::comment::CommentStyle;
+pub use self::fit::{fit, Fit};
+pub use self::frame::{Frame, FrameStyle};
+pub use self::layout::{LayoutMode, SmushRules};
+pub use self::locator::FontLocator;
+pub use self::registry::FontRegistry;
 
+mod cell;
+mod comment;
 mod figfont;
 mod wrapper;
 mod smusher;
+mod renderer;
+mod fit;
+mod frame;
+mod layout;
+mod locator;
+mod registry;
 
 #[derive(Debug)]
 pub enum Error {
@@ -20,7 +43,12 @@ pub enum Error {
     Io(io::Error),
     Parse(num::ParseIntError),
     CodeTag(u32),
+    Layout(i32),
+    FontNotFound(String, Vec<PathBuf>),
+    FrameStyle(String),
+    CommentStyle(String),
     LineFull,
+    TooTall(usize),
 }
 
 impl fmt::Display for Error {
@@ -30,7 +58,19 @@ impl fmt::Display for Error {
             Error::Io(ref err)       => write!(f, "{}", err),
             Error::Parse(ref err)    => write!(f, "Can't parse value: {}", err),
             Error::CodeTag(tag)      => write!(f, "Invalid code tag: {}", tag),
+            Error::Layout(val)       => write!(f, "Invalid layout mode: {}", val),
+            Error::FontNotFound(ref name, ref tried) => {
+                write!(f, "Font not found: {}", name)?;
+                if !tried.is_empty() {
+                    let v: Vec<String> = tried.iter().map(|x| x.display().to_string()).collect();
+                    write!(f, " (tried {})", v.join(", "))?;
+                }
+                Ok(())
+            },
+            Error::FrameStyle(ref s) => write!(f, "Invalid frame style: {}", s),
+            Error::CommentStyle(ref s) => write!(f, "Invalid comment style: {}", s),
             Error::LineFull          => write!(f, "Line is full"), 
+            Error::TooTall(rows)     => write!(f, "Output is taller than {} rows", rows),
         }
     }
 }
@@ -42,7 +82,12 @@ impl error::Error for Error {
             Error::Io(ref err)    => err.description(),
             Error::Parse(ref err) => err.description(),
             Error::CodeTag(_)     => "Invalid code tag",
+            Error::Layout(_)      => "Invalid layout mode",
+            Error::FontNotFound(..) => "Font not found",
+            Error::FrameStyle(_)  => "Invalid frame style",
+            Error::CommentStyle(_) => "Invalid comment style",
             Error::LineFull       => "Line full", 
+            Error::TooTall(_)     => "Output too tall",
         }
     }
 
@@ -0,0 +1,193 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use Error;
+use FIGfont;
+
+// Font file name suffixes to try, in order, if the font name has none.
+const SUFFIXES: [&str; 5] = [".rlf", ".flf", ".tlf", ".flf.zip", ".tlf.zip"];
+
+/// Find font files in a list of directories.
+///
+/// FontLocator searches an ordered list of directories for a font file, trying the
+/// .rlf extension of compiled fonts first, then the .flf and .tlf extensions and their
+/// zipped variants if the font name has no extension. A compiled font is used instead
+/// of the font it was compiled from in the same directory. If no file matches the name
+/// exactly, a case-insensitive match is accepted.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let mut locator = rustlet::FontLocator::new();
+/// locator.dirs.insert(0, "fonts".into());
+///
+/// // Find and load fonts by name
+/// let path = locator.find("small")?;
+/// let font = locator.load("Standard")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FontLocator {
+    pub dirs: Vec<PathBuf>,     // directories to search, in order
+}
+
+impl FontLocator {
+
+    /// Create a new font locator using the default search path: the directory in the
+    /// FIGLET_FONTDIR environment variable (if set), followed by the system and user
+    /// FIGlet font directories and the TOIlet font directory.
+    pub fn new() -> Self {
+        let mut dirs = Vec::new();
+        if let Some(dir) = env::var_os("FIGLET_FONTDIR") {
+            dirs.push(PathBuf::from(dir));
+        }
+        dirs.push(PathBuf::from("/usr/share/figlet"));
+        dirs.push(PathBuf::from("/usr/local/share/figlet"));
+        if let Some(home) = env::var_os("HOME") {
+            dirs.push(Path::new(&home).join(".local/share/figlet"));
+        }
+        dirs.push(PathBuf::from("/usr/share/toilet"));
+
+        FontLocator{
+            dirs,
+        }
+    }
+
+    /// Create a new font locator that searches the given directories.
+    pub fn with_dirs<P: AsRef<Path>>(dirs: &[P]) -> Self {
+        FontLocator{
+            dirs: dirs.iter().map(|x| x.as_ref().to_path_buf()).collect(),
+        }
+    }
+
+    /// Find the file containing the named font. Names containing a path separator are
+    /// not searched in the font directories.
+    ///
+    /// # Errors
+    ///
+    /// If the font can't be found, a FontNotFound error listing all paths tried is
+    /// returned.
+    pub fn find(&self, name: &str) -> Result<PathBuf, Error> {
+        let names: Vec<String> = match SUFFIXES.iter().any(|x| has_suffix(name, x)) {
+            true  => vec![name.to_owned()],
+            false => SUFFIXES.iter().map(|x| name.to_owned() + x).collect(),
+        };
+
+        let mut tried = Vec::new();
+        let paths: Vec<PathBuf> = match Path::new(name).parent() {
+            Some(p) if !p.as_os_str().is_empty() => names.iter().map(PathBuf::from).collect(),
+            _ => self.dirs.iter().flat_map(|d| names.iter().map(move |x| d.join(x))).collect(),
+        };
+
+        for path in paths {
+            if let Some(found) = find_file(&path) {
+                return Ok(found);
+            }
+            tried.push(path);
+        }
+
+        Err(Error::FontNotFound(name.to_owned(), tried))
+    }
+
+    /// Find and load the named font.
+    ///
+    /// # Errors
+    ///
+    /// If the font can't be found, a FontNotFound error is returned. Errors loading
+    /// the font file are also returned.
+    pub fn load(&self, name: &str) -> Result<FIGfont, Error> {
+        FIGfont::from_path(self.find(name)?)
+    }
+}
+
+impl Default for FontLocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn has_suffix(name: &str, suffix: &str) -> bool {
+    name.to_lowercase().ends_with(suffix)
+}
+
+// Find a file with the given path, or a file in the same directory whose name differs
+// only in case.
+fn find_file(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+
+    let name = path.file_name()?.to_str()?.to_lowercase();
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _                                    => Path::new("."),
+    };
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir).ok()?.filter_map(|x| x.ok())
+        .map(|x| x.path())
+        .filter(|x| x.is_file() && x.file_name().and_then(|n| n.to_str())
+                .is_some_and(|n| n.to_lowercase() == name))
+        .collect();
+    entries.sort();
+    entries.into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fonts")
+    }
+
+    fn test_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+    }
+
+    #[test]
+    fn test_find() {
+        let locator = FontLocator::with_dirs(&[test_dir(), font_dir()]);
+        assert_eq!(locator.find("small").unwrap(), font_dir().join("small.flf"));
+        assert_eq!(locator.find("small.flf").unwrap(), font_dir().join("small.flf"));
+        assert_eq!(locator.find("test").unwrap(), test_dir().join("test.flf"));
+        assert_eq!(locator.find("test.flf.zip").unwrap(), test_dir().join("test.flf.zip"));
+
+        // names with a path are not searched in the font directories
+        let path = font_dir().join("mini");
+        assert_eq!(locator.find(path.to_str().unwrap()).unwrap(), font_dir().join("mini.flf"));
+    }
+
+    #[test]
+    fn test_find_case() {
+        let locator = FontLocator::with_dirs(&[font_dir()]);
+        assert_eq!(locator.find("Standard").unwrap(), font_dir().join("standard.flf"));
+        assert_eq!(locator.find("SMALL.FLF").unwrap(), font_dir().join("small.flf"));
+    }
+
+    #[test]
+    fn test_not_found() {
+        let locator = FontLocator::with_dirs(&[test_dir(), font_dir()]);
+        match locator.find("nonexistent") {
+            Err(Error::FontNotFound(name, tried)) => {
+                assert_eq!(name, "nonexistent");
+                assert_eq!(tried.len(), 2 * SUFFIXES.len());
+                assert_eq!(tried[0], test_dir().join("nonexistent.rlf"));
+                assert_eq!(tried[1], test_dir().join("nonexistent.flf"));
+                assert_eq!(tried[5], font_dir().join("nonexistent.rlf"));
+                assert_eq!(tried[9], font_dir().join("nonexistent.tlf.zip"));
+            },
+            _ => panic!("expected FontNotFound error"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zip")]
+    fn test_load_zip() {
+        let locator = FontLocator::with_dirs(&[test_dir()]);
+        let font = locator.load("test.flf.zip").unwrap();
+        let plain = locator.load("test").unwrap();
+        assert_eq!(font.height, plain.height);
+        assert_eq!(font.get('A').get(), plain.get('A').get());
+    }
+}
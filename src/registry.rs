@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use Error;
+use FIGfont;
+use FontLocator;
+
+/// A thread-safe cache of loaded fonts.
+///
+/// FontRegistry loads fonts by name the first time they are requested, using a
+/// FontLocator to find the font files, and keeps them for later use. Fonts are
+/// returned as `Arc<FIGfont>`, so they can be shared between threads without being
+/// parsed again.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let registry = rustlet::FontRegistry::new(rustlet::FontLocator::new());
+///
+/// // Parsed only once, then shared
+/// let font = registry.get("standard")?;
+/// let mut sm = rustlet::Smusher::new(font);
+/// sm.push_str("hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FontRegistry {
+    pub locator: FontLocator,                           // used to find font files
+    fonts      : Mutex<HashMap<String, Arc<FIGfont>>>,  // fonts loaded so far, by name
+}
+
+impl FontRegistry {
+
+    /// Create a new, empty font registry that finds fonts using the given locator.
+    pub fn new(locator: FontLocator) -> Self {
+        FontRegistry{
+            locator,
+            fonts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Obtain the named font, loading it if it's not in the registry yet.
+    ///
+    /// # Errors
+    ///
+    /// If the font is not in the registry and can't be found or loaded, the error
+    /// from FontLocator is returned and nothing is added to the registry.
+    pub fn get(&self, name: &str) -> Result<Arc<FIGfont>, Error> {
+        if let Some(font) = self.lock().get(name) {
+            return Ok(font.clone());
+        }
+
+        // don't hold the lock while parsing, another thread may have loaded the font
+        // in the meantime and in that case its copy is kept
+        let font = Arc::new(self.locator.load(name)?);
+        Ok(self.lock().entry(name.to_owned()).or_insert(font).clone())
+    }
+
+    /// Add a font to the registry under the given name, replacing any font previously
+    /// registered with the same name.
+    pub fn insert(&self, name: &str, font: FIGfont) -> Arc<FIGfont> {
+        let font = Arc::new(font);
+        self.lock().insert(name.to_owned(), font.clone());
+        font
+    }
+
+    /// Verify whether a font with the given name is in the registry.
+    pub fn contains(&self, name: &str) -> bool {
+        self.lock().contains_key(name)
+    }
+
+    /// Remove all fonts from the registry. Fonts still in use elsewhere are not
+    /// affected.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    // A panic in another thread can't leave the map in an inconsistent state, so a
+    // poisoned lock is still usable.
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<FIGfont>>> {
+        self.fonts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn registry() -> FontRegistry {
+        let dir = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts";
+        FontRegistry::new(FontLocator::with_dirs(&[dir]))
+    }
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<FIGfont>();
+        assert_send_sync::<FontRegistry>();
+    }
+
+    #[test]
+    fn test_get() {
+        let registry = registry();
+        assert!(!registry.contains("small"));
+        let font = registry.get("small").unwrap();
+        assert!(registry.contains("small"));
+        assert!(Arc::ptr_eq(&font, &registry.get("small").unwrap()));
+
+        assert!(matches!(registry.get("nonexistent"), Err(Error::FontNotFound(..))));
+        assert!(!registry.contains("nonexistent"));
+
+        registry.clear();
+        assert!(!registry.contains("small"));
+        assert_eq!(font.height, 5);
+    }
+
+    #[test]
+    fn test_insert() {
+        let registry = registry();
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        let font = registry.insert("test", FIGfont::from_path(&path).unwrap());
+        assert!(Arc::ptr_eq(&font, &registry.get("test").unwrap()));
+    }
+
+    #[test]
+    fn test_threads() {
+        let registry = Arc::new(registry());
+        let handles: Vec<_> = (0..4).map(|_| {
+            let registry = registry.clone();
+            thread::spawn(move || registry.get("standard").unwrap())
+        }).collect();
+
+        let fonts: Vec<Arc<FIGfont>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let font = registry.get("standard").unwrap();
+        assert!(fonts.iter().all(|x| Arc::ptr_eq(x, &font)));
+    }
+}
@@ -0,0 +1,243 @@
+use std::io::Write;
+use std::ops::Deref;
+use Error;
+use wrapper::{tokens, RowStyle};
+use {FIGfont, Wrapper};
+
+/// Render ASCII-art text directly to an output stream.
+///
+/// Renderer feeds its input text to a Wrapper and writes each line of output to the
+/// given `io::Write` as soon as the wrapper flushes it, so rendered text doesn't have
+/// to be collected in memory before being printed. If the wrapper places the
+/// output in a box, with a fixed height or top and bottom margins, the output is held
+/// until `flush` is called and the box is drawn around all of it. Errors from the
+/// output stream are returned to the caller instead of causing a panic.
+pub struct Renderer<F: Deref<Target=FIGfont>, W: Write> {
+    wr  : Wrapper<F>,    // the line wrapper
+    out : W,             // the output stream
+    rows: Vec<String>,   // output held until flush
+}
+
+impl<F: Deref<Target=FIGfont>, W: Write> Renderer<F, W> {
+
+    /// Create a new renderer using the specified Wrapper and output stream.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 80);
+    ///
+    /// // Render text to the standard output
+    /// let stdout = std::io::stdout();
+    /// let mut r = rustlet::Renderer::new(wr, stdout.lock());
+    /// r.write_line("hello")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(wr: Wrapper<F>, out: W) -> Self {
+        Renderer{
+            wr,
+            out,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the line wrapper.
+    pub fn wrapper(&mut self) -> &mut Wrapper<F> {
+        &mut self.wr
+    }
+
+    /// Consume this renderer, returning the underlying output stream.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Render a line of text, wrapping it if necessary, and write the output.
+    ///
+    /// The output buffer is cleared before and written after rendering the line, so an
+    /// empty line produces empty output lines.
+    ///
+    /// # Errors
+    ///
+    /// If writing to the output stream fails, an Io error is returned.
+    pub fn write_line(&mut self, s: &str) -> Result<(), Error> {
+        self.wr.clear_buffer();
+        self.write_tokens(s)?;
+        let v = self.wr.get();
+        self.wr.clear_buffer();
+        self.write_rows(&v)
+    }
+
+    /// Render a line of text as part of a paragraph.
+    ///
+    /// Lines are joined together until a blank line or a line starting with whitespace
+    /// is found, in which case the current paragraph is written. See
+    /// `Wrapper::wrap_paragraph` for details. Call `flush` after the last line to write
+    /// the pending output.
+    ///
+    /// # Errors
+    ///
+    /// If writing to the output stream fails, an Io error is returned.
+    pub fn write_paragraph(&mut self, s: &str) -> Result<(), Error> {
+        let style = self.stream_style();
+        let (out, rows) = (&mut self.out, &mut self.rows);
+        self.wr.try_wrap_paragraph(s, &mut |v| write_rows(out, rows, style, v))
+    }
+
+    /// Write the contents of the output buffer, if any, and flush the output stream.
+    /// Output held to be placed in a box is finished and written, and later output
+    /// starts a new box.
+    ///
+    /// # Errors
+    ///
+    /// If writing to the output stream fails, an Io error is returned. If the output
+    /// is taller than the wrapper height, a TooTall error is returned.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        {
+            let style = self.stream_style();
+            let (out, rows) = (&mut self.out, &mut self.rows);
+            self.wr.try_end_paragraph(&mut |v| write_rows(out, rows, style, v))?;
+        }
+        if !self.wr.is_empty() {
+            let v = self.wr.get();
+            self.wr.clear_buffer();
+            self.write_rows(&v)?;
+        }
+        if !self.rows.is_empty() {
+            let rows = self.wr.finish(&self.rows)?;
+            self.rows.clear();
+            write_lines(&mut self.out, &rows)?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn write_tokens(&mut self, s: &str) -> Result<(), Error> {
+        let style = self.stream_style();
+        let (out, rows) = (&mut self.out, &mut self.rows);
+        for token in tokens(s) {
+            self.wr.try_wrap_str(token, &mut |v| write_rows(out, rows, style, v))?;
+        }
+        Ok(())
+    }
+
+    fn write_rows(&mut self, v: &[String]) -> Result<(), Error> {
+        let style = self.stream_style();
+        write_rows(&mut self.out, &mut self.rows, style, v)
+    }
+
+    // Get the row decorations to write rows as they are flushed, or None if rows are
+    // placed in a box and must be held until flush.
+    fn stream_style(&self) -> Option<RowStyle> {
+        match self.wr.has_box() {
+            true  => None,
+            false => Some(self.wr.row_style()),
+        }
+    }
+}
+
+// Write rows flushed by the wrapper, or hold them if they are placed in a box.
+fn write_rows<W: Write>(out: &mut W, rows: &mut Vec<String>, style: Option<RowStyle>,
+                        v: &[String]) -> Result<(), Error> {
+    match style {
+        Some(style) => write_lines(out, &v.iter().map(|x| style.apply(x.clone())).collect::<Vec<_>>()),
+        None        => { rows.extend_from_slice(v); Ok(()) },
+    }
+}
+
+fn write_lines<W: Write>(out: &mut W, v: &[String]) -> Result<(), Error> {
+    for line in v {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::Arc;
+    use Smusher;
+
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn test_font() -> FIGfont {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        FIGfont::from_path(&path).unwrap()
+    }
+
+    #[test]
+    fn test_write_line() {
+        let font = test_font();
+        let mut r = Renderer::new(Wrapper::new(Smusher::new(&font), 8), Vec::new());
+        r.write_line("this is a test").unwrap();
+        r.write_line("").unwrap();
+        let out = String::from_utf8(r.into_inner()).unwrap();
+        assert_eq!(out, "this is \na test\n\n");
+    }
+
+    #[test]
+    fn test_write_paragraph() {
+        let font = test_font();
+        let mut r = Renderer::new(Wrapper::new(Smusher::new(&font), 12), Vec::new());
+        r.write_paragraph("this is").unwrap();
+        r.write_paragraph("a test").unwrap();
+        r.write_paragraph(" new").unwrap();
+        r.write_paragraph("").unwrap();
+        r.write_paragraph("last").unwrap();
+        r.flush().unwrap();
+        let out = String::from_utf8(r.into_inner()).unwrap();
+        assert_eq!(out, "this is a \ntest\n new\nlast\n");
+    }
+
+    // the renderer owns its font and can be returned from a function
+    fn owned_renderer() -> Renderer<Arc<FIGfont>, Vec<u8>> {
+        let font = Arc::new(test_font());
+        Renderer::new(Wrapper::new(Smusher::new(font), 8), Vec::new())
+    }
+
+    #[test]
+    fn test_owned() {
+        let mut r = owned_renderer();
+        r.write_line("this is a test").unwrap();
+        let out = String::from_utf8(r.into_inner()).unwrap();
+        assert_eq!(out, "this is \na test\n");
+    }
+
+    #[test]
+    fn test_margins() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 8);
+        wr.margins.top = 1;
+        wr.margins.left = 1;
+        let mut r = Renderer::new(wr, Vec::new());
+        r.write_line("this is").unwrap();
+        r.write_line("a test").unwrap();
+        assert!(r.rows.len() == 2);
+        r.flush().unwrap();
+        let out = String::from_utf8(r.into_inner()).unwrap();
+        assert_eq!(out, "\n this is\n a test\n");
+    }
+
+    #[test]
+    fn test_io_error() {
+        let font = test_font();
+        let mut r = Renderer::new(Wrapper::new(Smusher::new(&font), 8), BrokenPipe);
+        match r.write_line("this is a test") {
+            Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::BrokenPipe),
+            _                 => panic!("expected I/O error"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use figfont;
+use layout::{LayoutMode, SmushRules};
+use smusher::rules::{RuleSet, SmushRule};
 
 macro_rules! cmp_return_other {
     ( $a:expr, $b:expr, $c:expr ) => {
@@ -48,20 +49,38 @@ macro_rules! apply_rule {
 }
 
 
-pub fn smush(l: char, r: char, hardblank: char, right2left: bool, mode: u32) -> Option<char> {
+// The specification rules, in the order they are applied.
+static BUILTIN: [(SmushRules, &dyn SmushRule); 6] = [
+    (SmushRules::HARDBLANK, &HardblankRule),
+    (SmushRules::EQUAL,     &EqualRule),
+    (SmushRules::UNDERLINE, &UnderlineRule),
+    (SmushRules::HIERARCHY, &HierarchyRule),
+    (SmushRules::PAIR,      &PairRule),
+    (SmushRules::BIGX,      &BigXRule),
+];
+
+pub fn smush(l: char, r: char, hardblank: char, right2left: bool, mode: LayoutMode,
+             custom: &RuleSet) -> Option<char> {
 
     cmp_return_other!(' ', l, r);
 
-    // Kerning mode
-    if mode & figfont::SMUSH_KERN != 0 {
-        return None;
+    let rules = match mode {
+        LayoutMode::FullWidth | LayoutMode::Fitting => return None,
+        LayoutMode::Smushing{ rules }               => rules,
+        LayoutMode::Universal                       => SmushRules::empty(),
+    };
+
+    // Custom rules are consulted before the rules from the layout mode, but not for
+    // hardblanks, so they can't remove the space kept by hardblanks
+    if l != hardblank && r != hardblank {
+        apply_rule!(custom.smush(l, r, hardblank));
     }
 
     // Universal smushing simply overrides the sub-character from the earlier
     // FIGcharacter with the sub-character from the later FIGcharacter. This
     // produces an "overlapping" effect with some FIGfonts, wherin the latter
     // FIGcharacter may appear to be "in front".
-    if mode == 0 {
+    if rules.is_empty() {
         // Ensure overlapping preference to visible characters
         //cmp_return_other!(hardblank, l, r);
 
@@ -79,92 +98,100 @@ pub fn smush(l: char, r: char, hardblank: char, right2left: bool, mode: u32) ->
         return Some(r)
     }
 
-    // Rule 6: HARDBLANK SMUSHING (code value 32)
-    // Smushes two hardblanks together, replacing them with a single hardblank.
-    if l == hardblank && r == hardblank {
-        if mode & figfont::SMUSH_HARDBLANK != 0 {
-            return Some(l);
-        } else {
-            return None;
-        }
+    // Two hardblanks can only be smushed by rule 6
+    if l == hardblank && r == hardblank && !rules.contains(SmushRules::HARDBLANK) {
+        return None;
     }
 
-    apply_rule!(rule_1(l, r, mode));
-    apply_rule!(rule_2(l, r, mode));
-    apply_rule!(rule_3(l, r, mode));
-    apply_rule!(rule_4(l, r, mode));
-    apply_rule!(rule_5(l, r, mode));
+    for &(flag, rule) in BUILTIN.iter() {
+        if rules.contains(flag) {
+            apply_rule!(rule.smush(l, r, hardblank));
+        }
+    }
 
     None
 }
 
+/// Rule 1, equal character smushing: two equal sub-characters other than hardblanks
+/// are smushed into one.
+#[derive(Debug, Clone, Copy)]
+pub struct EqualRule;
 
-// Rule 1: EQUAL CHARACTER SMUSHING (code value 1)
-// Two sub-characters are smushed into a single sub-character if they are the same (except
-// hardblanks). 
-fn rule_1(l: char, r: char, mode: u32) -> Option<char> {
-    if mode & figfont::SMUSH_EQUAL != 0 {
-        if l == r {
+impl SmushRule for EqualRule {
+    fn smush(&self, l: char, r: char, hardblank: char) -> Option<char> {
+        if l == r && l != hardblank {
             return Some(l)
         }
+        None
     }
-
-    None
 }
 
-// Rule 2: UNDERSCORE SMUSHING (code value 2)
-// An underscore ("_") will be replaced by any of: "|", "/", "\", "[", "]", "{", "}", "(",
-// ")", "<" or ">".
-fn rule_2(l: char, r: char, mode: u32) -> Option<char> {
-    if mode & figfont::SMUSH_UNDERLINE != 0 {
+/// Rule 2, underscore smushing: an underscore is replaced by any of `|/\[]{}()<>`.
+#[derive(Debug, Clone, Copy)]
+pub struct UnderlineRule;
+
+impl SmushRule for UnderlineRule {
+    fn smush(&self, l: char, r: char, _: char) -> Option<char> {
         find_return_latter!("_", r"|/\[]{}()<>", l, r);
+        None
     }
-
-    None
 }
 
-// Rule 3: HIERARCHY SMUSHING (code value 4)
-// A hierarchy of six classes is used: "|", "/\", "[]", "{}", "()", and "<>". When two
-// smushing sub-characters are from different classes, the one from the latter class will
-// be used.
-fn rule_3(l: char, r: char, mode: u32) -> Option<char> {
-    if mode & figfont::SMUSH_HIERARCHY != 0 {
+/// Rule 3, hierarchy smushing: of two sub-characters from different classes in
+/// `|`, `/\`, `[]`, `{}`, `()` and `<>`, the one from the latter class is kept.
+#[derive(Debug, Clone, Copy)]
+pub struct HierarchyRule;
+
+impl SmushRule for HierarchyRule {
+    fn smush(&self, l: char, r: char, _: char) -> Option<char> {
         find_return_latter!("|", r"/\[]{}()<>", l, r);
         find_return_latter!(r"/\", "[]{}()<>", l, r);
         find_return_latter!("[]", "{}()<>", l, r);
         find_return_latter!("{}", "()<>", l, r);
         find_return_latter!("()", "<>", l, r);
+        None
     }
-
-    None
 }
 
+/// Rule 4, opposite pair smushing: opposing brackets, braces and parentheses are
+/// replaced by a vertical bar.
+#[derive(Debug, Clone, Copy)]
+pub struct PairRule;
 
-// Rule 4: OPPOSITE PAIR SMUSHING (code value 8)
-// Smushes opposing brackets ("[]" or "]["), braces ("{}" or "}{") and parentheses ("()"
-// or ")(") together, replacing any such pair with a vertical bar ("|").
-fn rule_4(l: char, r: char, mode: u32) -> Option<char> {
-    if mode & figfont::SMUSH_PAIR != 0 {
+impl SmushRule for PairRule {
+    fn smush(&self, l: char, r: char, _: char) -> Option<char> {
         cmp_any_return!('[', ']', l, r, '|');
         cmp_any_return!('{', '}', l, r, '|');
         cmp_any_return!('(', ')', l, r, '|');
+        None
     }
-
-    None
 }
 
-// Rule 5: BIG X SMUSHING (code value 16)
-// Smushes "/\" into "|", "\/" into "Y", and "><" into "X". Note that "<>" is not smushed
-// in any way by this rule. The name "BIG X" is historical; originally all three pairs
-// were smushed into "X".
-fn rule_5(l: char, r: char, mode: u32) -> Option<char> {
-    if mode & figfont::SMUSH_BIGX != 0 {
+/// Rule 5, big X smushing: `/\` becomes `|`, `\/` becomes `Y` and `><` becomes `X`.
+/// The name is historical, originally all three pairs were smushed into `X`.
+#[derive(Debug, Clone, Copy)]
+pub struct BigXRule;
+
+impl SmushRule for BigXRule {
+    fn smush(&self, l: char, r: char, _: char) -> Option<char> {
         cmp_return!('/', '\\', l, r, '|');
         cmp_return!('\\', '/', l, r, 'Y');
         cmp_return!('>', '<', l, r, 'X');
+        None
     }
+}
 
-    None
+/// Rule 6, hardblank smushing: two hardblanks are smushed into one.
+#[derive(Debug, Clone, Copy)]
+pub struct HardblankRule;
+
+impl SmushRule for HardblankRule {
+    fn smush(&self, l: char, r: char, hardblank: char) -> Option<char> {
+        if l == hardblank && r == hardblank {
+            return Some(l);
+        }
+        None
+    }
 }
 
 
@@ -174,46 +201,35 @@ mod tests {
 
     #[test]
     fn test_rule_1() {
-        assert_eq!(rule_1('-', '-', 0), None);
-        assert_eq!(rule_1('-', 'x', 0), None);
-        assert_eq!(rule_1('-', '-', figfont::SMUSH_EQUAL), Some('-'));
-        assert_eq!(rule_1('-', 'x', figfont::SMUSH_EQUAL), None);
+        assert_eq!(EqualRule.smush('-', '-', '$'), Some('-'));
+        assert_eq!(EqualRule.smush('-', 'x', '$'), None);
+        assert_eq!(EqualRule.smush('$', '$', '$'), None);
     }
 
     #[test]
     fn test_rule_2() {
         for x in [ '|', '/', '\\', '[', ']', '{', '}', '(', ')', '<', '>' ].iter() {
-            assert_eq!(rule_2('_', *x, 0), None);
-            assert_eq!(rule_2(*x, '_', 0), None);
-            assert_eq!(rule_2('_', *x, figfont::SMUSH_UNDERLINE), Some(*x));
-            assert_eq!(rule_2(*x, '_', figfont::SMUSH_UNDERLINE), Some(*x));
+            assert_eq!(UnderlineRule.smush('_', *x, '$'), Some(*x));
+            assert_eq!(UnderlineRule.smush(*x, '_', '$'), Some(*x));
         }
-        assert_eq!(rule_2('_', 'x', 0), None);
-        assert_eq!(rule_2('x', '_', 0), None);
-        assert_eq!(rule_2('_', 'x', figfont::SMUSH_UNDERLINE), None);
-        assert_eq!(rule_2('x', '_', figfont::SMUSH_UNDERLINE), None);
+        assert_eq!(UnderlineRule.smush('_', 'x', '$'), None);
+        assert_eq!(UnderlineRule.smush('x', '_', '$'), None);
     }
 
     #[test]
     fn test_rule_3() {
         let list = [ '|', '/', '\\', '[', ']', '{', '}', '(', ')', '<', '>' ];
 
-        for x in list.iter() {
-            for y in list.iter() {
-                assert_eq!(rule_3(*x, *y, 0), None);
-            }
-        }
-
-        assert_eq!(rule_3('|', '|', figfont::SMUSH_HIERARCHY), None);
+        assert_eq!(HierarchyRule.smush('|', '|', '$'), None);
         for x in list[1..].iter() {
-            assert_eq!(rule_3('|', *x, figfont::SMUSH_HIERARCHY), Some(*x));
+            assert_eq!(HierarchyRule.smush('|', *x, '$'), Some(*x));
         }
         for i in 0..4 {
             let idx = 3 + i*2;
             for x in list[idx..idx+1].iter() {
                 for y in list[idx+2..].iter() {
-                    assert_eq!(rule_3(*x, *y, figfont::SMUSH_HIERARCHY), Some(*y));
-                    assert_eq!(rule_3(*y, *x, figfont::SMUSH_HIERARCHY), Some(*y));
+                    assert_eq!(HierarchyRule.smush(*x, *y, '$'), Some(*y));
+                    assert_eq!(HierarchyRule.smush(*y, *x, '$'), Some(*y));
                 }
             }
         }
@@ -221,33 +237,50 @@ mod tests {
 
     #[test]
     fn test_rule_4() {
-        assert_eq!(rule_4('[', ']', 0), None);
-        assert_eq!(rule_4(']', '[', 0), None);
-        assert_eq!(rule_4('{', '}', 0), None);
-        assert_eq!(rule_4('}', '{', 0), None);
-        assert_eq!(rule_4('(', ')', 0), None);
-        assert_eq!(rule_4(')', '(', 0), None);
-        assert_eq!(rule_4('[', ']', figfont::SMUSH_PAIR), Some('|'));
-        assert_eq!(rule_4(']', '[', figfont::SMUSH_PAIR), Some('|'));
-        assert_eq!(rule_4('{', '}', figfont::SMUSH_PAIR), Some('|'));
-        assert_eq!(rule_4('}', '{', figfont::SMUSH_PAIR), Some('|'));
-        assert_eq!(rule_4('(', ')', figfont::SMUSH_PAIR), Some('|'));
-        assert_eq!(rule_4(')', '(', figfont::SMUSH_PAIR), Some('|'));
-        assert_eq!(rule_4('(', 'x', figfont::SMUSH_PAIR), None);
-        assert_eq!(rule_4('(', '}', figfont::SMUSH_PAIR), None);
-        assert_eq!(rule_4('(', ']', figfont::SMUSH_PAIR), None);
-        assert_eq!(rule_4('(', '(', figfont::SMUSH_PAIR), None);
+        assert_eq!(PairRule.smush('[', ']', '$'), Some('|'));
+        assert_eq!(PairRule.smush(']', '[', '$'), Some('|'));
+        assert_eq!(PairRule.smush('{', '}', '$'), Some('|'));
+        assert_eq!(PairRule.smush('}', '{', '$'), Some('|'));
+        assert_eq!(PairRule.smush('(', ')', '$'), Some('|'));
+        assert_eq!(PairRule.smush(')', '(', '$'), Some('|'));
+        assert_eq!(PairRule.smush('(', 'x', '$'), None);
+        assert_eq!(PairRule.smush('(', '}', '$'), None);
+        assert_eq!(PairRule.smush('(', ']', '$'), None);
+        assert_eq!(PairRule.smush('(', '(', '$'), None);
     }
 
     #[test]
     fn test_rule_5() {
-        assert_eq!(rule_5('/', '\\', 0), None);
-        assert_eq!(rule_5('\\', '/', 0), None);
-        assert_eq!(rule_5('>', '<', 0), None);
-        assert_eq!(rule_5('<', '>', 0), None);
-        assert_eq!(rule_5('/', '\\', figfont::SMUSH_BIGX), Some('|'));
-        assert_eq!(rule_5('\\', '/', figfont::SMUSH_BIGX), Some('Y'));
-        assert_eq!(rule_5('>', '<', figfont::SMUSH_BIGX), Some('X'));
-        assert_eq!(rule_5('<', '>', figfont::SMUSH_BIGX), None);
+        assert_eq!(BigXRule.smush('/', '\\', '$'), Some('|'));
+        assert_eq!(BigXRule.smush('\\', '/', '$'), Some('Y'));
+        assert_eq!(BigXRule.smush('>', '<', '$'), Some('X'));
+        assert_eq!(BigXRule.smush('<', '>', '$'), None);
+    }
+
+    #[test]
+    fn test_layout_rules() {
+        let none = RuleSet::new();
+
+        // only the rules in the layout mode are applied
+        let mode = LayoutMode::Smushing{ rules: SmushRules::EQUAL };
+        assert_eq!(smush('-', '-', '$', false, mode, &none), Some('-'));
+        assert_eq!(smush('(', ')', '$', false, mode, &none), None);
+        let mode = LayoutMode::Smushing{ rules: SmushRules::PAIR | SmushRules::BIGX };
+        assert_eq!(smush('(', ')', '$', false, mode, &none), Some('|'));
+        assert_eq!(smush('>', '<', '$', false, mode, &none), Some('X'));
+        assert_eq!(smush('-', '-', '$', false, mode, &none), None);
+    }
+
+    #[test]
+    fn test_custom_hardblank() {
+        let mut custom = RuleSet::new();
+        custom.push(|_, _, _| Some('#'));
+
+        // custom rules are not consulted for hardblanks
+        let mode = LayoutMode::Smushing{ rules: SmushRules::HARDBLANK };
+        assert_eq!(smush('a', 'b', '$', false, mode, &custom), Some('#'));
+        assert_eq!(smush('$', 'b', '$', false, mode, &custom), None);
+        assert_eq!(smush('a', '$', '$', false, mode, &custom), None);
+        assert_eq!(smush('$', '$', '$', false, mode, &custom), Some('$'));
     }
 }
@@ -1,10 +1,30 @@
-use std::cmp::min;
+use std::cmp::{max, min};
+use std::ops::Deref;
 pub use figfont::{FIGchar, FIGfont};
 pub use wrapper::Wrapper;
+use cell;
+use layout::LayoutMode;
+pub use self::charsmush::{BigXRule, EqualRule, HardblankRule, HierarchyRule, PairRule, UnderlineRule};
+pub use self::rules::{RuleSet, SmushRule};
+#[cfg(feature = "serde")] use serde::{Deserialize, Serialize};
 
 mod charsmush;
+mod rules;
 pub mod strsmush;
 
+/// How control characters other than newline and tab are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ControlChars {
+    /// Control characters are ignored.
+    Drop,
+    /// ASCII control characters are rendered in caret notation (e.g. `^C`), other
+    /// control characters are ignored.
+    Caret,
+    /// Control characters are rendered using the given character.
+    Replace(char),
+}
+
 /// Creates a message written with ASCII-art characters.
 ///
 /// The Smusher adds FIGcharacters to an output buffer and controls how they fit
@@ -12,17 +32,123 @@ pub mod strsmush;
 /// the FIGfont layout mode. Possible layout modes include full-width, kerning (when
 /// FIGcharacters are moved closer to each other but without overlapping borders),
 /// or smushing (where borders overlap).
+///
+/// The font can be anything that dereferences to a FIGfont, such as `&FIGfont`,
+/// `Rc<FIGfont>` or `Arc<FIGfont>`, so a smusher can borrow, own or share its font.
+///
+/// A newline character starts a new line of FIGcharacters below the current one. Tabs
+/// are expanded to `tab_width` blanks, and other control characters are handled as
+/// specified in `control`.
 #[derive(Debug)]
-pub struct Smusher<'a> {
-    pub mode      : u32,          // the layout mode
-    pub full_width: bool,
-    pub right2left: bool,
-    font          : &'a FIGfont,
-    output        : Vec<String>,
+pub struct Smusher<F: Deref<Target=FIGfont>> {
+    pub mode        : LayoutMode,   // the layout mode
+    pub right2left  : bool,
+    pub tab_width   : usize,        // number of blanks a tab is expanded to
+    pub control     : ControlChars, // how to render control characters
+    pub custom_rules: RuleSet,      // smushing rules consulted before the layout mode rules
+    font            : F,
+    output          : Vec<Vec<char>>, // output rows, one vector of sub-characters per line
+    done            : Vec<Vec<char>>, // output rows of lines finished with a newline
+    history         : Vec<Checkpoint>, // saved states used to undo added characters
+}
+
+// The part of the output buffer changed by adding a FIGcharacter, saved so the
+// operation can be undone.
+#[derive(Debug)]
+struct Checkpoint {
+    ch   : Option<char>, // the character added, or None if it's rendered with more glyphs
+    amt  : usize,       // number of overlapping sub-characters
+    lens : Vec<usize>,  // length of each output line before adding the character
+    saved: Vec<char>,   // overlapped sub-characters of all lines, concatenated
+}
+
+impl Checkpoint {
+    fn new(ch: Option<char>, output: &[Vec<char>], amt: usize) -> Self {
+        let mut cp = Checkpoint{
+            ch,
+            amt,
+            lens : Vec::with_capacity(output.len()),
+            saved: Vec::new(),
+        };
+        for line in output {
+            let start = line.len() - min(amt, line.len());
+            cp.lens.push(line.len());
+            cp.saved.extend_from_slice(&line[start..]);
+        }
+        cp
+    }
+
+    fn restore(self, output: &mut [Vec<char>]) {
+        let mut saved = self.saved.into_iter();
+        for (line, &len) in output.iter_mut().zip(&self.lens) {
+            let start = len - min(self.amt, len);
+            line.truncate(start);
+            line.extend(saved.by_ref().take(len - start));
+        }
+    }
+}
+
+
+// The end of an output line, which is all we need to know to compute how the next
+// FIGcharacter fits into the line.
+#[derive(Debug, Clone, Copy)]
+struct LineEnd {
+    len  : usize,   // line length in sub-characters
+    last : char,    // last visible sub-character, or a blank if the line is empty
+    trail: usize,   // number of whitespace sub-characters after the last visible one
 }
 
+impl LineEnd {
+    fn new(line: &[char]) -> Self {
+        let (last, trail) = strsmush::line_end(line);
+        LineEnd{
+            len: line.len(),
+            last,
+            trail,
+        }
+    }
+
+    // Update the line end as if the glyph line were smushed into the line. Glyph
+    // sub-characters never overlap visible sub-characters before the last one, so the
+    // new line end can be computed from the current one.
+    fn smush(&mut self, s2: &str, amt: usize, hardblank: char, mode: LayoutMode, rules: &RuleSet) {
+        let l1 = self.len;
+        let skip = amt.saturating_sub(l1);
+        let m1 = l1 - min(amt, l1);
+        let p = match self.last {
+            ' ' => None,
+            _   => Some(l1 - self.trail - 1),
+        };
+
+        let mut l2 = 0;
+        let mut visible = None;
+        for (i, r) in s2.chars().skip(skip).enumerate() {
+            l2 = i + 1;
+            if !r.is_whitespace() {
+                visible = Some((m1 + i, r));
+            }
+        }
+        self.len = max(l1, m1 + l2);
+
+        let (pos, last) = match (visible, p) {
+            (Some((pos, r)), Some(p)) if pos == p => {
+                (p, charsmush::smush(self.last, r, hardblank, false, mode, rules).unwrap_or(r))
+            },
+            (Some((pos, r)), Some(p)) if pos > p  => (pos, r),
+            (Some(v), None)                       => v,
+            (_, Some(p))                          => (p, self.last),
+            (None, None)                          => {
+                self.last = ' ';
+                self.trail = self.len;
+                return;
+            },
+        };
+        self.last = last;
+        self.trail = self.len - pos - 1;
+    }
+}
 
-impl<'a> Smusher<'a> {
+impl<F: Deref<Target=FIGfont>> Smusher<F> {
 
     /// Create a new smusher using the specified FIGfont.
     ///
@@ -35,40 +161,54 @@ impl<'a> Smusher<'a> {
     ///
     /// // Create a smusher using the FIGfont
     /// let mut sm = rustlet::Smusher::new(&font);
+    ///
+    /// // Create a smusher that owns its FIGfont
+    /// let font = std::sync::Arc::new(rustlet::FIGfont::from_path("small.flf")?);
+    /// let mut sm = rustlet::Smusher::new(font);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new(font: &'a FIGfont) -> Self {
+    pub fn new(font: F) -> Self {
+        let mode = LayoutMode::from_full_layout(font.layout);
         let mut sm = Smusher{
             font,
-            mode      : font.layout,
-            full_width: font.old_layout == -1,
-            right2left: false,
-            output    : Vec::new(),
+            mode,
+            right2left  : false,
+            tab_width   : DEFAULT_TAB_WIDTH,
+            control     : ControlChars::Drop,
+            custom_rules: RuleSet::new(),
+            output      : Vec::new(),
+            done        : Vec::new(),
+            history     : Vec::new(),
         };
         for _ in 0..sm.font.height {
-            sm.output.push("".to_string());
+            sm.output.push(Vec::new());
         }
         sm
     }
 
+    /// Get a reference to the FIGfont used by this smusher.
+    pub fn font(&self) -> &FIGfont {
+        &self.font
+    }
+
     /// Get the contents of the output buffer.
+    /// Lines finished with a newline come first, followed by the current line.
     pub fn get(&self) -> Vec<String> {
-        let mut res: Vec<String> = Vec::new();
-        for line in &self.output {
-            res.push(line.replace(&self.font.hardblank.to_string(), " "));
-        }
-        res
+        let (hardblank, table) = (self.font.hardblank, self.font.clusters());
+        self.done.iter().chain(&self.output).map(|line| cell::from_cells(line, hardblank, table)).collect()
     }
 
     /// Verify whether output buffer is empty.
     pub fn is_empty(&self) -> bool {
-        self.output.len() == 0 || self.output[0].is_empty()
+        self.done.is_empty() && (self.output.len() == 0 || self.output[0].is_empty())
     }
 
     /// Clear the output buffer.
     pub fn clear(&mut self) {
         self.output.iter_mut().for_each(|x| x.clear());
+        self.done.clear();
+        self.history.clear();
     }
 
     /// Add a string to the output buffer, applying the smushing rules specified in the font
@@ -80,106 +220,444 @@ impl<'a> Smusher<'a> {
     /// Add a character to the output buffer, applying the smushing rules specified in the font
     /// layout.
     pub fn push(&mut self, ch: char) {
-        let fc = self.font.get(ch);
-        self.output = smush(&self.output, fc, self.font.hardblank, self.full_width, self.mode);
+        let mut first = Some(ch);
+        let (tab_width, control) = (self.tab_width, self.control);
+        let line_break = !glyphs(ch, tab_width, control, |g| {
+            self.push_glyph(first.take(), g);
+        });
+
+        if line_break {
+            self.history.push(Checkpoint::new(Some(ch), &[], 0));
+            let height = self.output.len();
+            self.done.append(&mut self.output);
+            self.output.resize(height, Vec::new());
+        } else if first.is_some() {
+            // nothing rendered, but pop must still remove the character
+            self.history.push(Checkpoint::new(first, &self.output, 0));
+        }
+    }
+
+    fn push_glyph(&mut self, ch: Option<char>, g: char) {
+        let fc = self.font.get(g);
+        let amt = match self.mode {
+            LayoutMode::FullWidth => 0,
+            _                     => amount(self.output.iter().map(|x| LineEnd::new(x)), fc,
+                                            self.font.hardblank, self.mode, &self.custom_rules),
+        };
+        self.history.push(Checkpoint::new(ch, &self.output, amt));
+        smush(&mut self.output, fc, amt, self.font.hardblank, self.mode, &self.custom_rules);
+    }
+
+    /// Remove the last character added to the output buffer, restoring the buffer to its
+    /// exact previous state. Returns the removed character, or None if there's nothing to
+    /// remove.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let mut sm = rustlet::Smusher::new(&font);
+    ///
+    /// sm.push_str("hello");
+    /// assert_eq!(sm.pop(), Some('o'));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pop(&mut self) -> Option<char> {
+        loop {
+            let cp = self.history.pop()?;
+            match cp.ch {
+                Some('\n') => {
+                    let start = self.done.len() - self.output.len();
+                    self.output = self.done.split_off(start);
+                    return Some('\n');
+                },
+                Some(ch) => {
+                    cp.restore(&mut self.output);
+                    return Some(ch);
+                },
+                None => cp.restore(&mut self.output),
+            }
+        }
+    }
+
+    /// Remove characters from the output buffer until only the first `len` characters
+    /// added to it remain. Has no effect if fewer characters were added.
+    pub fn truncate(&mut self, len: usize) {
+        let count = self.history.iter().filter(|x| x.ch.is_some()).count();
+        (len..count).for_each(|_| { self.pop(); });
     }
 
-    /// Obtain the size, in sub-characters, of any line of the output buffer.
+    /// Obtain the display width, in terminal columns, of any row of the current line of
+    /// the output buffer.
     pub fn len(&self) -> usize {
-        let s: &str = &self.output[0];
-        s.chars().count()
+        self.output[0].len()
     }
 
-    /// Limit the size, in sub-characters, of the output buffer. If the buffer is longer than
-    /// the specified size, the rightmost sub-characters will be removed.
+    /// Obtain the display width, in terminal columns, the current line of the output buffer
+    /// would have if the given string were added to it. If the string has newlines, the
+    /// width of the widest line is returned. The output buffer is not changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let mut sm = rustlet::Smusher::new(&font);
+    ///
+    /// sm.push_str("hello");
+    /// let width = sm.measure(" world");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn measure(&self, s: &str) -> usize {
+        let mut ends: Vec<LineEnd> = self.output.iter().map(|x| LineEnd::new(x)).collect();
+        let mut width = 0;
+        for ch in s.chars() {
+            let font = &self.font;
+            if !glyphs(ch, self.tab_width, self.control, |g| {
+                advance(&mut ends, font.get(g), font.hardblank, self.mode, &self.custom_rules)
+            }) {
+                width = max(width, ends.first().map_or(0, |x| x.len));
+                ends.iter_mut().for_each(|x| *x = LineEnd::new(&[]));
+            }
+        }
+        max(width, ends.first().map_or(0, |x| x.len))
+    }
+
+    /// Limit the display width, in terminal columns, of the output buffer. If the buffer is
+    /// wider than the specified width, the rightmost sub-characters will be removed. Characters added
+    /// before trimming can't be removed with `pop` or `truncate`.
     pub fn trim(&mut self, width: usize) {
-        self.output = trim(&self.output, width);
+        trim(&mut self.output, width);
+        trim(&mut self.done, width);
+        self.history.clear();
     }
 }
 
-fn amount(output: &Vec<String>, c: &FIGchar, hardblank: char, mode: u32) -> usize {
-    let mut amt = 9999;
-    for (line, cline) in output.iter().zip(&c.get()) {
-        amt = min(amt, strsmush::amount(&line, &cline, hardblank, mode));
+const DEFAULT_TAB_WIDTH: usize = 1;   // same as FIGlet
+
+// Call f for each glyph used to render a character. Returns false if the character is
+// a line break.
+fn glyphs<F: FnMut(char)>(ch: char, tab_width: usize, control: ControlChars, mut f: F) -> bool {
+    match ch {
+        '\n'                 => return false,
+        '\t'                 => (0..tab_width).for_each(|_| f(' ')),
+        _ if ch.is_control() => match control {
+            ControlChars::Caret if ch.is_ascii() => {
+                f('^');
+                f((ch as u8 ^ 0x40) as char);
+            },
+            ControlChars::Replace(c) => f(c),
+            _                        => {},
+        },
+        _                    => f(ch),
     }
-    amt
+    true
 }
 
-fn trim(output: &Vec<String>, width: usize) -> Vec<String> {
-    output.iter().map(|line| {
-        let s: &str = &line;
-        let index = s.char_indices().nth(width).unwrap().0;
-        s[..index].to_string()
-    }).collect()
+/// Compute the size, in terminal columns and rows, of a string rendered using the specified
+/// FIGfont and layout mode. Returns the width and height of the rendered text. Control
+/// characters are handled as in a Smusher with default settings, so newlines start a
+/// new line of FIGcharacters.
+///
+/// The text is not actually rendered, so this is much cheaper than adding the text to a
+/// Smusher, and can be used to evaluate many fonts or layout modes. No custom smushing
+/// rules are used; see `measure_with_rules` to measure text as rendered by a Smusher with
+/// custom rules.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let font = rustlet::FIGfont::from_path("small.flf")?;
+/// let mode = rustlet::LayoutMode::Fitting;
+/// let (width, height) = rustlet::measure(&font, "hello", mode);
+/// # Ok(())
+/// # }
+/// ```
+pub fn measure(font: &FIGfont, text: &str, mode: LayoutMode) -> (usize, usize) {
+    measure_with_rules(font, text, mode, &RuleSet::new())
 }
 
-fn smush(output: &Vec<String>, c: &FIGchar, hardblank: char, full_width: bool, mode: u32) -> Vec<String> {
+/// Compute the size of a string rendered using the specified FIGfont, layout mode and
+/// custom smushing rules, as in `measure`.
+///
+/// # Examples
+///
+/// ```
+/// # fn foo() -> Result<(), Box<std::error::Error>> {
+/// let font = rustlet::FIGfont::from_path("small.flf")?;
+/// let mut sm = rustlet::Smusher::new(&font);
+/// sm.custom_rules.push(|l, r, _| if (l, r) == ('-', '|') { Some('+') } else { None });
+///
+/// let (width, height) = rustlet::measure_with_rules(&font, "hello", sm.mode, &sm.custom_rules);
+/// # Ok(())
+/// # }
+/// ```
+pub fn measure_with_rules(font: &FIGfont, text: &str, mode: LayoutMode, rules: &RuleSet)
+                          -> (usize, usize) {
+    let mut ends = vec![LineEnd::new(&[]); font.height];
+    let (mut width, mut lines) = (0, 1);
+    for ch in text.chars() {
+        if !glyphs(ch, DEFAULT_TAB_WIDTH, ControlChars::Drop, |g| {
+            advance(&mut ends, font.get(g), font.hardblank, mode, rules)
+        }) {
+            width = max(width, ends.first().map_or(0, |x| x.len));
+            ends.iter_mut().for_each(|x| *x = LineEnd::new(&[]));
+            lines += 1;
+        }
+    }
+    (max(width, ends.first().map_or(0, |x| x.len)), lines * font.height)
+}
 
-    let amt = match full_width {
-        true  => 0,
-        false => amount(&output, c, hardblank, mode),
+fn advance(ends: &mut [LineEnd], c: &FIGchar, hardblank: char, mode: LayoutMode, rules: &RuleSet) {
+    let amt = match mode {
+        LayoutMode::FullWidth => 0,
+        _                     => amount(ends.iter().cloned(), c, hardblank, mode, rules),
     };
+    for (end, cline) in ends.iter_mut().zip(c.cells()) {
+        end.smush(cline, amt, hardblank, mode, rules);
+    }
+}
 
-    let mut res = Vec::new();
-
-    for (line, cline) in output.iter().zip(&c.get()) {
-        res.push(strsmush::smush(&line, &cline, amt, hardblank, mode));
+fn amount<I: Iterator<Item=LineEnd>>(ends: I, c: &FIGchar, hardblank: char, mode: LayoutMode,
+                                     rules: &RuleSet) -> usize {
+    let mut amt = 9999;
+    for (end, cline) in ends.zip(c.cells()) {
+        amt = min(amt, strsmush::amount(end.last, end.trail, cline, hardblank, mode, rules));
     }
+    amt
+}
+
+fn trim(output: &mut [Vec<char>], width: usize) {
+    output.iter_mut().for_each(|line| line.truncate(width));
+}
 
-    res
+fn smush(output: &mut [Vec<char>], c: &FIGchar, amt: usize, hardblank: char, mode: LayoutMode,
+         rules: &RuleSet) {
+    for (line, cline) in output.iter_mut().zip(c.cells()) {
+        strsmush::smush(line, cline, amt, hardblank, mode, rules);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use layout::SmushRules;
+    use std::fs;
+    use std::path::PathBuf;
 
-    macro_rules! vec_of_strings {
-        ( $($x:expr),* ) => (vec![$($x.to_string()),*])
+    const ALL: LayoutMode = LayoutMode::Smushing{ rules: SmushRules::all() };
+
+    macro_rules! vec_of_chars {
+        ( $($x:expr),* ) => (vec![$($x.chars().collect::<Vec<char>>()),*])
     }
 
     #[test]
     fn test_amount() {
-        let output = vec_of_strings![ "", "", "", "" ];
+        let output = vec_of_chars![ "", "", "", "" ];
         let fc = FIGchar::from_lines(&vec![ "   ", "  x", " xx", "xx " ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 0);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 0);
 
-        let output = vec_of_strings![ "", "", "", "" ];
+        let output = vec_of_chars![ "", "", "", "" ];
         let fc = FIGchar::from_lines(&vec![ "   ", "  x", " xx", "   " ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 1);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 1);
 
-        let output = vec_of_strings![ "xxx ", "xx  ", "x   ", "    " ];
+        let output = vec_of_chars![ "xxx ", "xx  ", "x   ", "    " ];
         let fc = FIGchar::from_lines(&vec![ "   y", "  yy", " yyy", "yyyy" ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 4);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 4);
 
-        let output = vec_of_strings![  "xxxx ", "xxx  ", "xx   ", "x    " ];
+        let output = vec_of_chars![  "xxxx ", "xxx  ", "xx   ", "x    " ];
         let fc = FIGchar::from_lines(&vec![ "   x", "  xx", " xxx", "xxxx" ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 5);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 5);
     }
 
     #[test]
     fn test_amount_utf8() {
-        let output = vec_of_strings![ "", "", "", "" ];
+        let output = vec_of_chars![ "", "", "", "" ];
         let fc = FIGchar::from_lines(&vec![ "   ", "  á", " áá", "   " ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 1);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 1);
 
-        let output = vec_of_strings![ "ááá ", "áá  ", "á   ", "    " ];
+        let output = vec_of_chars![ "ááá ", "áá  ", "á   ", "    " ];
         let fc = FIGchar::from_lines(&vec![ "   é", "  éé", " ééé", "éééé" ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 4);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 4);
 
-        let output = vec_of_strings![  "áááá ", "ááá  ", "áá   ", "á    " ];
+        let output = vec_of_chars![  "áááá ", "ááá  ", "áá   ", "á    " ];
         let fc = FIGchar::from_lines(&vec![ "   á", "  áá", " ááá", "áááá" ]).unwrap();
-        assert_eq!(amount(&output, &fc, '$', 0xbf), 5);
+        assert_eq!(amount(output.iter().map(|x| LineEnd::new(x)), &fc, '$', ALL, &RuleSet::new()), 5);
+    }
+
+    #[test]
+    fn test_pop() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/small.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+        let text = "Smushy [text] /\\/ ><";
+
+        let mut sm = Smusher::new(&font);
+        sm.push_str(text);
+        for i in (0..text.len()).rev() {
+            let mut expected = Smusher::new(&font);
+            expected.push_str(&text[..i]);
+            assert_eq!(sm.pop(), text[i..].chars().next());
+            assert_eq!(sm.get(), expected.get());
+        }
+        assert_eq!(sm.pop(), None);
+        assert!(sm.is_empty());
+    }
+
+    #[test]
+    fn test_pop_control() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/small.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+        let text = "ab\ncd\te\x07f\n";
+
+        let mut sm = Smusher::new(&font);
+        sm.tab_width = 4;
+        sm.control = ControlChars::Caret;
+        sm.push_str(text);
+        for (i, ch) in text.char_indices().rev() {
+            let mut expected = Smusher::new(&font);
+            expected.tab_width = 4;
+            expected.control = ControlChars::Caret;
+            expected.push_str(&text[..i]);
+            assert_eq!(sm.pop(), Some(ch));
+            assert_eq!(sm.get(), expected.get());
+        }
+        assert!(sm.is_empty());
+
+        sm.push_str(text);
+        sm.truncate(4);
+        assert_eq!(sm.get().len(), 2 * font.height);
+    }
+
+    #[test]
+    fn test_newline() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+
+        let mut sm = Smusher::new(&font);
+        sm.push_str("abc\nd");
+        assert_eq!(sm.get(), vec!["abc", "d"]);
+        assert_eq!(sm.len(), 1);
+        assert_eq!(sm.measure("e\nfghi"), 4);
+        assert_eq!(sm.measure("efgh\ni"), 5);
+        assert!(!sm.is_empty());
+
+        sm.push('\n');
+        assert_eq!(sm.get(), vec!["abc", "d", ""]);
+        assert_eq!(measure(&font, "abc\nd\n", ALL), (3, 3));
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+
+        let mut sm = Smusher::new(&font);
+        sm.mode = LayoutMode::Smushing{ rules: SmushRules::EQUAL };
+        sm.push_str("-|--");
+        assert_eq!(sm.get(), vec!["-|-"]);
+
+        // custom rules are consulted before the layout mode rules
+        sm.clear();
+        sm.custom_rules.push(|l, r, _| if (l, r) == ('-', '|') { Some('+') } else { None });
+        sm.custom_rules.push(|_, _, _| Some('='));
+        assert_eq!(sm.measure("-|--"), 1);
+        assert_eq!(measure_with_rules(&font, "-|--", sm.mode, &sm.custom_rules), (1, 1));
+        assert_eq!(measure(&font, "-|--", sm.mode), (3, 1));
+        sm.push_str("-|--");
+        assert_eq!(sm.get(), vec!["="]);
+        sm.pop();
+        sm.pop();
+        assert_eq!(sm.get(), vec!["+"]);
+
+        // no smushing in fitting mode
+        sm.clear();
+        sm.mode = LayoutMode::Fitting;
+        sm.push_str("-|");
+        assert_eq!(sm.get(), vec!["-|"]);
+    }
+
+    #[test]
+    fn test_tab_control() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+
+        let mut sm = Smusher::new(&font);
+        sm.push_str("a\tb\x1bc\r");
+        assert_eq!(sm.get(), vec!["a bc"]);
+
+        sm.clear();
+        sm.tab_width = 3;
+        sm.control = ControlChars::Caret;
+        sm.push_str("a\tb\x1bc\x7f\u{85}");
+        assert_eq!(sm.get(), vec!["a   b^[c^?"]);
+
+        sm.clear();
+        sm.control = ControlChars::Replace('?');
+        sm.push_str("a\x1bc");
+        assert_eq!(sm.get(), vec!["a?c"]);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts/standard.flf";
+        let font = FIGfont::from_path(&path).unwrap();
+
+        let mut sm = Smusher::new(&font);
+        sm.push_str("Hello world");
+        sm.truncate(20);
+        sm.truncate(5);
+
+        let mut expected = Smusher::new(&font);
+        expected.push_str("Hello");
+        assert_eq!(sm.get(), expected.get());
+
+        sm.trim(3);
+        assert_eq!(sm.pop(), None);
+    }
+
+    #[test]
+    fn test_measure() {
+        let texts = [ "Hello world", "Smushy [text] /\\/ ><", "  x  ", "", "Ação! ಠ_ಠ" ];
+        let mut fonts: Vec<_> = fs::read_dir(env!("CARGO_MANIFEST_DIR").to_owned() + "/fonts")
+            .unwrap().map(|x| x.unwrap().path()).collect();
+        fonts.push(PathBuf::from(env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf"));
+
+        for path in fonts {
+            let font = FIGfont::from_path(&path).unwrap();
+            for &mode in [ LayoutMode::from_full_layout(font.layout), LayoutMode::Fitting,
+                           LayoutMode::Universal, LayoutMode::FullWidth ].iter() {
+                for text in texts.iter() {
+                    let mut sm = Smusher::new(&font);
+                    sm.mode = mode;
+                    sm.push_str(text);
+                    assert_eq!(measure(&font, text, mode), (sm.len(), font.height),
+                               "{:?} {:?} mode {:?}", path, text, mode);
+
+                    sm.clear();
+                    sm.push_str("x");
+                    let width = sm.measure(text);
+                    sm.push_str(text);
+                    assert_eq!(width, sm.len());
+                }
+            }
+        }
     }
 
     #[test]
     fn test_trim() {
-        let output = vec_of_strings![ "12345", "abcde" ];
-        assert_eq!(trim(&output, 3), vec_of_strings![ "123", "abc" ]);
+        let mut output = vec_of_chars![ "12345", "abcde" ];
+        trim(&mut output, 3);
+        assert_eq!(output, vec_of_chars![ "123", "abc" ]);
     }
 
     #[test]
     fn test_trim_utf8() {
-        let output = vec_of_strings![ "12345", "áéíóú" ];
-        assert_eq!(trim(&output, 3), vec_of_strings![ "123", "áéí" ]);
+        let mut output = vec_of_chars![ "12345", "áéíóú" ];
+        trim(&mut output, 3);
+        assert_eq!(output, vec_of_chars![ "123", "áéí" ]);
     }
 }
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::sync::Arc;
+
+/// A horizontal smushing rule.
+///
+/// A smushing rule decides whether two touching sub-characters, one from the end of
+/// the output and one from the start of the FIGcharacter being added, can be smushed
+/// into a single sub-character. Rules are never called for blanks. Besides the six
+/// rules in the FIGfont specification, custom rules can be added to a Smusher with a
+/// RuleSet. Custom rules are not called for hardblanks either, so they can't remove
+/// the space kept by hardblanks. Closures taking the left and right sub-characters and
+/// the hardblank are also rules.
+///
+/// # Examples
+///
+/// ```
+/// // Merge box-drawing lines into junctions
+/// struct BoxRule;
+///
+/// impl rustlet::SmushRule for BoxRule {
+///     fn smush(&self, l: char, r: char, _hardblank: char) -> Option<char> {
+///         match (l, r) {
+///             ('─', '│') | ('│', '─') => Some('┼'),
+///             _                       => None,
+///         }
+///     }
+/// }
+///
+/// let mut rules = rustlet::RuleSet::new();
+/// rules.push(BoxRule);
+/// assert_eq!(rules.smush('─', '│', '$'), Some('┼'));
+/// ```
+pub trait SmushRule: Send + Sync {
+    /// Smush the sub-characters, returning the resulting sub-character or None if
+    /// this rule doesn't apply to them.
+    fn smush(&self, l: char, r: char, hardblank: char) -> Option<char>;
+}
+
+impl<F: Fn(char, char, char) -> Option<char> + Send + Sync> SmushRule for F {
+    fn smush(&self, l: char, r: char, hardblank: char) -> Option<char> {
+        self(l, r, hardblank)
+    }
+}
+
+/// An ordered list of smushing rules.
+///
+/// Rules in the set are consulted in order, and the first rule that applies decides
+/// the result. A Smusher consults its custom rule set before the rules given by the
+/// layout mode, so custom rules can extend or override the specification rules in
+/// smushing modes, except for hardblanks. Rule sets are cheap to clone, as rules are
+/// shared.
+#[derive(Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<Arc<dyn SmushRule>>,   // rules, in the order they are consulted
+}
+
+impl RuleSet {
+
+    /// Create an empty rule set.
+    pub fn new() -> Self {
+        RuleSet{
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a rule to be consulted after all rules in the set.
+    pub fn push<R: SmushRule + 'static>(&mut self, rule: R) {
+        self.rules.push(Arc::new(rule));
+    }
+
+    /// Add a rule to be consulted before all rules in the set.
+    pub fn push_front<R: SmushRule + 'static>(&mut self, rule: R) {
+        self.rules.insert(0, Arc::new(rule));
+    }
+
+    /// Get the number of rules in the set.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Verify whether the set has no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Remove all rules from the set.
+    pub fn clear(&mut self) {
+        self.rules.clear();
+    }
+
+    /// Smush the sub-characters using the first rule in the set that applies. Returns
+    /// None if no rule applies.
+    pub fn smush(&self, l: char, r: char, hardblank: char) -> Option<char> {
+        self.rules.iter().filter_map(|x| x.smush(l, r, hardblank)).next()
+    }
+}
+
+impl fmt::Debug for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RuleSet({} rules)", self.rules.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smusher::charsmush::{EqualRule, PairRule};
+
+    #[test]
+    fn test_order() {
+        let mut rules = RuleSet::new();
+        assert!(rules.is_empty());
+        assert_eq!(rules.smush('(', ')', '$'), None);
+
+        rules.push(PairRule);
+        rules.push(|_, _, _| Some('*'));
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules.smush('(', ')', '$'), Some('|'));
+        assert_eq!(rules.smush('a', 'b', '$'), Some('*'));
+
+        rules.push_front(|l: char, r: char, _| if l == r { Some('=') } else { None });
+        assert_eq!(rules.smush('(', '(', '$'), Some('='));
+
+        rules.clear();
+        rules.push(EqualRule);
+        assert_eq!(rules.smush('x', 'x', '$'), Some('x'));
+        assert_eq!(rules.smush('$', '$', '$'), None);
+    }
+}
@@ -1,38 +1,25 @@
-use smusher::charsmush;
-
-trait CharExt {
-    fn char_len(&self) -> usize;
-    fn char_index(&self, usize) -> usize;
-}
-
-impl<'a> CharExt for &'a str {
-    fn char_len(&self) -> usize {
-        self.chars().count()
-    }
 
-    fn char_index(&self, i: usize) -> usize {
-        self.char_indices().nth(i).unwrap().0
+use std::cmp::min;
+use layout::LayoutMode;
+use smusher::charsmush;
+use smusher::rules::RuleSet;
+
+// Find the last non-whitespace sub-character of an output row (or a blank if there's
+// none) and the number of whitespace sub-characters after it.
+pub fn line_end(s1: &[char]) -> (char, usize) {
+    match s1.iter().rposition(|c| !c.is_whitespace()) {
+        Some(pos) => (s1[pos], s1.len() - pos - 1),
+        None      => (' ', s1.len()),
     }
 }
 
-// Compute the number of characters a string can be smushed into another string.
-pub fn amount(s1: &str, s2: &str, hardblank: char, mode: u32) -> usize {
+// Compute the number of characters a glyph line can be smushed into an output row,
+// given the last visible sub-character of the row and the amount of trailing whitespace.
+pub fn amount(l: char, trail: usize, s2: &str, hardblank: char, mode: LayoutMode,
+              rules: &RuleSet) -> usize {
 
-    let mut v1 = s1.chars().rev();
     let mut v2 = s2.chars();
-    let mut amt = 0;
-       
-    let mut l = ' ';
-    if !s1.is_empty() {
-        l = v1.next().unwrap();
-        while l.is_whitespace() {
-            amt += 1;
-            l = match v1.next() {
-                Some(val) => val,
-                None      => break,
-            };
-        }
-    }
+    let mut amt = trail;
 
     let mut r = ' ';
     if !s2.is_empty() {
@@ -50,143 +37,145 @@ pub fn amount(s1: &str, s2: &str, hardblank: char, mode: u32) -> usize {
         return amt;
     }
 
-    match charsmush::smush(l, r, hardblank, false, mode) {
+    match charsmush::smush(l, r, hardblank, false, mode, rules) {
         Some(_) => { amt + 1},
         None    => { amt },
     }
 }
 
-pub fn smush(s1: &str, s2x: &str, mut amt: usize, hardblank: char, mode: u32) -> String {
+// Smush a glyph line into an output row, overlapping amt characters. Only the
+// overlapping region of the row is changed, the rest of the glyph line is appended.
+pub fn smush(s1: &mut Vec<char>, s2: &str, amt: usize, hardblank: char, mode: LayoutMode,
+             rules: &RuleSet) {
 
-    if s2x.is_empty() {
-        return s1.to_owned();
-    }
-
-    let mut s2 = s2x;
-    let l1 = s1.char_len();
+    let l1 = s1.len();
 
-    if amt > l1 {
-        s2 = &s2[s2.char_index(amt-l1)..];
-        amt = l1;
-    }
+    // if the overlap is larger than the row, skip the first characters of s2
+    let skip = amt.saturating_sub(l1);
+    let m1 = l1 - min(amt, l1);
 
-    let l2 = s2.char_len();
-    let mut res = "".to_owned();
-    let m1 = l1 - amt;
-
-    // part 1: only characters from s1
-    // don't use the index operator, we want characters not bytes
-    let mut v1 = s1.chars();
-    (0..m1).for_each(|_| res.push(v1.next().unwrap()));
+    for (i, r) in s2.chars().skip(skip).enumerate() {
+        let pos = m1 + i;
+        if pos >= l1 {
+            // past the end of s1, only characters from s2
+            s1.push(r);
+            continue;
+        }
 
-    // part 2: s1 and s2 overlap
-    let mut v2 = s2.chars();
-    (0..l2).for_each(|_| {
-        let l = match v1.next() {
-            Some(v) => v,
-            None    => ' ',
-        };
-        let r = v2.next().unwrap();
-        if l != ' ' && r != ' ' {
-            match charsmush::smush(l, r, hardblank, false, mode) {
-                Some(c) => res.push(c),
-                None    => res.push(r),
+        // s1 and s2 overlap
+        let l = s1[pos];
+        s1[pos] = if l != ' ' && r != ' ' {
+            match charsmush::smush(l, r, hardblank, false, mode, rules) {
+                Some(c) => c,
+                None    => r,
             }
+        } else if l == ' ' {
+            r
         } else {
-            res.push(if l == ' ' { r } else { l });
-        }
-    });
-
-    // part 3: remainder of s1 after the end of s2
-    // don't use the index operator, we want characters not bytes
-    let m2 = m1 + l2;
-    (m2..l1).for_each(|_| res.push(v1.next().unwrap()));
-
-    res
+            l
+        };
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use layout::SmushRules;
+
+    const ALL: LayoutMode = LayoutMode::Smushing{ rules: SmushRules::all() };
+
+    fn amount_str(s1: &str, s2: &str, hardblank: char, mode: LayoutMode) -> usize {
+        let (l, trail) = line_end(&s1.chars().collect::<Vec<char>>());
+        amount(l, trail, s2, hardblank, mode, &RuleSet::new())
+    }
+
+    fn smush_str(s1: &str, s2: &str, amt: usize, hardblank: char, mode: LayoutMode) -> String {
+        let mut v = s1.chars().collect();
+        smush(&mut v, s2, amt, hardblank, mode, &RuleSet::new());
+        v.into_iter().collect()
+    }
 
     #[test]
-    fn test_char_len() {
-        assert_eq!("aeiou".char_len(), 5);
-        assert_eq!("áéíóú".char_len(), 5);
+    fn test_line_end() {
+        assert_eq!(line_end(&[]), (' ', 0));
+        assert_eq!(line_end(&[' ', ' ']), (' ', 2));
+        assert_eq!(line_end(&['x', 'y', ' ']), ('y', 1));
+        assert_eq!(line_end(&['x', '$']), ('$', 0));
     }
 
     #[test]
     fn test_amount() {
-        assert_eq!(amount("", "", '$', 0xbf), 0);
-
-        assert_eq!(amount("", "    ", '$', 0xbf), 4);
-        assert_eq!(amount("", "   y", '$', 0xbf), 3);
-
-        assert_eq!(amount("    ", "    ", '$', 0xbf), 8);
-        assert_eq!(amount("x   ", "    ", '$', 0xbf), 7);
-        assert_eq!(amount("xx  ", "    ", '$', 0xbf), 6);
-        assert_eq!(amount("xxx ", "    ", '$', 0xbf), 5);
-        assert_eq!(amount("xxxx", "    ", '$', 0xbf), 4);
-
-        assert_eq!(amount("    ", "   y", '$', 0xbf), 7);
-        assert_eq!(amount("x   ", "   y", '$', 0xbf), 6);
-        assert_eq!(amount("xx  ", "   y", '$', 0xbf), 5);
-        assert_eq!(amount("xxx ", "   y", '$', 0xbf), 4);
-        assert_eq!(amount("xxxx", "   y", '$', 0xbf), 3);
-
-        assert_eq!(amount("    ", "  yy", '$', 0xbf), 6);
-        assert_eq!(amount("x   ", "  yy", '$', 0xbf), 5);
-        assert_eq!(amount("xx  ", "  yy", '$', 0xbf), 4);
-        assert_eq!(amount("xxx ", "  yy", '$', 0xbf), 3);
-        assert_eq!(amount("xxxx", "  yy", '$', 0xbf), 2);
-
-        assert_eq!(amount("    ", " yyy", '$', 0xbf), 5);
-        assert_eq!(amount("x   ", " yyy", '$', 0xbf), 4);
-        assert_eq!(amount("xx  ", " yyy", '$', 0xbf), 3);
-        assert_eq!(amount("xxx ", " yyy", '$', 0xbf), 2);
-        assert_eq!(amount("xxxx", " yyy", '$', 0xbf), 1);
-
-        assert_eq!(amount("    ", "yyyy", '$', 0xbf), 4);
-        assert_eq!(amount("x   ", "yyyy", '$', 0xbf), 3);
-        assert_eq!(amount("xx  ", "yyyy", '$', 0xbf), 2);
-        assert_eq!(amount("xxx ", "yyyy", '$', 0xbf), 1);
-        assert_eq!(amount("xxxx", "yyyy", '$', 0xbf), 0);
-
-        assert_eq!(amount("x", "y", '$', 0xbf), 0);
-        assert_eq!(amount("x", "x", '$', 0xbf), 1);     // rule 1
-        assert_eq!(amount("<", ">", '$', 0xbf), 0);
-        assert_eq!(amount("_", "/", '$', 0xbf), 1);     // rule 2
-        assert_eq!(amount("/", "_", '$', 0xbf), 1);     // rule 2
-        assert_eq!(amount("[", "{", '$', 0xbf), 1);     // rule 3
-        assert_eq!(amount("[", "]", '$', 0xbf), 1);     // rule 4
-        assert_eq!(amount(">", "<", '$', 0xbf), 1);     // rule 5
-        assert_eq!(amount("[ ", " {", '$', 0xbf), 3);   // rule 3 + spacing
+        assert_eq!(amount_str("", "", '$', ALL), 0);
+
+        assert_eq!(amount_str("", "    ", '$', ALL), 4);
+        assert_eq!(amount_str("", "   y", '$', ALL), 3);
+
+        assert_eq!(amount_str("    ", "    ", '$', ALL), 8);
+        assert_eq!(amount_str("x   ", "    ", '$', ALL), 7);
+        assert_eq!(amount_str("xx  ", "    ", '$', ALL), 6);
+        assert_eq!(amount_str("xxx ", "    ", '$', ALL), 5);
+        assert_eq!(amount_str("xxxx", "    ", '$', ALL), 4);
+
+        assert_eq!(amount_str("    ", "   y", '$', ALL), 7);
+        assert_eq!(amount_str("x   ", "   y", '$', ALL), 6);
+        assert_eq!(amount_str("xx  ", "   y", '$', ALL), 5);
+        assert_eq!(amount_str("xxx ", "   y", '$', ALL), 4);
+        assert_eq!(amount_str("xxxx", "   y", '$', ALL), 3);
+
+        assert_eq!(amount_str("    ", "  yy", '$', ALL), 6);
+        assert_eq!(amount_str("x   ", "  yy", '$', ALL), 5);
+        assert_eq!(amount_str("xx  ", "  yy", '$', ALL), 4);
+        assert_eq!(amount_str("xxx ", "  yy", '$', ALL), 3);
+        assert_eq!(amount_str("xxxx", "  yy", '$', ALL), 2);
+
+        assert_eq!(amount_str("    ", " yyy", '$', ALL), 5);
+        assert_eq!(amount_str("x   ", " yyy", '$', ALL), 4);
+        assert_eq!(amount_str("xx  ", " yyy", '$', ALL), 3);
+        assert_eq!(amount_str("xxx ", " yyy", '$', ALL), 2);
+        assert_eq!(amount_str("xxxx", " yyy", '$', ALL), 1);
+
+        assert_eq!(amount_str("    ", "yyyy", '$', ALL), 4);
+        assert_eq!(amount_str("x   ", "yyyy", '$', ALL), 3);
+        assert_eq!(amount_str("xx  ", "yyyy", '$', ALL), 2);
+        assert_eq!(amount_str("xxx ", "yyyy", '$', ALL), 1);
+        assert_eq!(amount_str("xxxx", "yyyy", '$', ALL), 0);
+
+        assert_eq!(amount_str("x", "y", '$', ALL), 0);
+        assert_eq!(amount_str("x", "x", '$', ALL), 1);     // rule 1
+        assert_eq!(amount_str("<", ">", '$', ALL), 0);
+        assert_eq!(amount_str("_", "/", '$', ALL), 1);     // rule 2
+        assert_eq!(amount_str("/", "_", '$', ALL), 1);     // rule 2
+        assert_eq!(amount_str("[", "{", '$', ALL), 1);     // rule 3
+        assert_eq!(amount_str("[", "]", '$', ALL), 1);     // rule 4
+        assert_eq!(amount_str(">", "<", '$', ALL), 1);     // rule 5
+        assert_eq!(amount_str("[ ", " {", '$', ALL), 3);   // rule 3 + spacing
     }
 
     #[test]
     fn test_amount_utf8() {
-        assert_eq!(amount("", "   é", '$', 0xbf), 3);
-        assert_eq!(amount("á   ", "    ", '$', 0xbf), 7);
-        assert_eq!(amount("áá  ", "    ", '$', 0xbf), 6);
-        assert_eq!(amount("á   ", "   é", '$', 0xbf), 6);
-        assert_eq!(amount("áá  ", "   é", '$', 0xbf), 5);
-        assert_eq!(amount("á   ", "  éé", '$', 0xbf), 5);
-        assert_eq!(amount("áá  ", "  éé", '$', 0xbf), 4);
+        assert_eq!(amount_str("", "   é", '$', ALL), 3);
+        assert_eq!(amount_str("á   ", "    ", '$', ALL), 7);
+        assert_eq!(amount_str("áá  ", "    ", '$', ALL), 6);
+        assert_eq!(amount_str("á   ", "   é", '$', ALL), 6);
+        assert_eq!(amount_str("áá  ", "   é", '$', ALL), 5);
+        assert_eq!(amount_str("á   ", "  éé", '$', ALL), 5);
+        assert_eq!(amount_str("áá  ", "  éé", '$', ALL), 4);
     }
 
     #[test]
     fn test_smush() {
-        assert_eq!(smush("123! ", "xy", 1, '$', 0xbf), "123!xy".to_string());
-        assert_eq!(smush("123> ", "<y", 2, '$', 0xbf), "123Xy".to_string());
-        assert_eq!(smush("123! ", "   xy", 5, '$', 0xbf), "123xy".to_string());
-        assert_eq!(smush("123/ ", "   /y", 5, '$', 0xbf), "123/y".to_string());
-        assert_eq!(smush("", "   y", 3, '$', 0xbf), "y".to_string());
-        assert_eq!(smush("", "      ", 1, '$', 0xbf), "     ".to_string());
+        assert_eq!(smush_str("123! ", "xy", 1, '$', ALL), "123!xy".to_string());
+        assert_eq!(smush_str("123> ", "<y", 2, '$', ALL), "123Xy".to_string());
+        assert_eq!(smush_str("123! ", "   xy", 5, '$', ALL), "123xy".to_string());
+        assert_eq!(smush_str("123/ ", "   /y", 5, '$', ALL), "123/y".to_string());
+        assert_eq!(smush_str("", "   y", 3, '$', ALL), "y".to_string());
+        assert_eq!(smush_str("", "      ", 1, '$', ALL), "     ".to_string());
+        assert_eq!(smush_str("abc  ", "x", 4, '$', ALL), "axc  ".to_string());
     }
 
     #[test]
     fn test_smush_utf8() {
-        assert_eq!(smush("áéí! ", "óú", 1, '$', 0xbf), "áéí!óú".to_string());
-        assert_eq!(smush("", "   á", 3, '$', 0xbf), "á".to_string());
+        assert_eq!(smush_str("áéí! ", "óú", 1, '$', ALL), "áéí!óú".to_string());
+        assert_eq!(smush_str("", "   á", 3, '$', ALL), "á".to_string());
     }
 }
@@ -1,27 +1,62 @@
+use std::cmp::min;
+use std::ops::Deref;
+use cell;
 use Error;
-use Smusher;
+use {CommentStyle, FIGfont, Frame, Smusher};
+#[cfg(feature = "serde")] use serde::{Deserialize, Serialize};
 
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Align {
     Left,
     Right,
     Center,
 }
 
+/// Vertical alignment of rows inside a box of fixed height.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum VAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Blank space around the output rows, in terminal columns and rows.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Margins {
+    pub top   : usize,
+    pub right : usize,
+    pub bottom: usize,
+    pub left  : usize,
+}
+
 /// Render smushed ASCII-art characters with word wrapping.
 ///
 /// Wrapper receives string or character input and renders the corresponding
 /// FIGcharacters if the output text fits inside the maximum width specified on
 /// creation. The wrapper will flush the output buffer earlier if the line is
 /// too long, thus producing multiple “lines” of output text.
-pub struct Wrapper<'a> {
-    sm        : Smusher<'a>, // the FIGcharacter smusher
+pub struct Wrapper<F: Deref<Target=FIGfont>> {
+    sm        : Smusher<F>,  // the FIGcharacter smusher
     buffer    : String,      // buffer to keep our input text 
     has_space : bool,        // whether we should add an extra space
+    in_para   : bool,        // whether a paragraph is being rendered
+    paragraphs: usize,       // number of paragraphs finished so far
     pub width : usize,       // terminal width
     pub align : Align,       // text alignment
+    pub paragraph_spacing: usize,  // blank rows between paragraphs
+    pub margins: Margins,          // blank space around the finished output
+    pub frame  : Option<Frame>,    // border drawn around the finished output
+    pub comment: Option<CommentStyle>,  // comment syntax wrapped around each row
+    pub height : Option<usize>,    // fixed number of rows in the finished output
+    pub valign : VAlign,           // vertical alignment inside a fixed height box
+    pub fill   : bool,             // whether rows are padded to exactly width columns
+    pub trim_trailing: bool,       // whether trailing blanks are removed from rows
 }
 
-impl<'a> Wrapper<'a> {
+impl<F: Deref<Target=FIGfont>> Wrapper<F> {
 
     /// Create a new wrapper using the specified Smusher and terminal width.
     ///
@@ -38,24 +73,44 @@ impl<'a> Wrapper<'a> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new(sm: Smusher<'a>, width: usize) -> Self {
+    pub fn new(sm: Smusher<F>, width: usize) -> Self {
         Wrapper{
            sm,
            width,
            buffer   : String::new(),
            align    : Align::Left,
            has_space: true,
+           in_para  : false,
+           paragraphs: 0,
+           paragraph_spacing: 0,
+           margins  : Margins::default(),
+           frame    : None,
+           comment  : None,
+           height   : None,
+           valign   : VAlign::default(),
+           fill     : false,
+           trim_trailing: false,
         }
     }
 
-    /// Clear the output buffer.
+    /// Clear the output buffer and the paragraph state, so the next paragraph is not
+    /// preceded by spacing rows.
     pub fn clear(&mut self) {
+        self.clear_buffer();
+        self.in_para = false;
+        self.paragraphs = 0;
+    }
+
+    // Clear the output buffer, keeping the paragraph state.
+    pub(crate) fn clear_buffer(&mut self) {
         self.sm.clear();
         self.buffer.clear();
         self.has_space = true;
     }
 
-    /// Retrieve the output buffer lines.
+    /// Retrieve the output buffer lines, aligned inside the text width. Margins, the
+    /// fixed height box, frame and comment delimiters are not added; pass the complete
+    /// output to `finish` to add them.
     ///
     /// # Examples
     ///
@@ -76,25 +131,130 @@ impl<'a> Wrapper<'a> {
     /// # }
     /// ```
     pub fn get(&mut self) -> Vec<String> {
-        if self.len() > self.width {
-            self.sm.trim(self.width);
+        // rows of lines separated by newlines may have different widths
+        let width = self.text_width();
+        self.sm.get().iter().map(|row| {
+            let row = trim(row, width);
+            let w = width - min(cell::str_width(&row), width);
+            match self.align {
+                Align::Left   => row,
+                Align::Center => add_pad(row, w / 2),
+                Align::Right  => add_pad(row, w),
+            }
+        }).collect()
+    }
+
+    /// Finish the complete output, as collected from `get` and the flush callbacks. The
+    /// rows are placed in a box of `height` rows, if set, a single frame is drawn around
+    /// them, and the margins, row padding and comment delimiters are added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let mut wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 20);
+    /// wr.margins.top = 1;
+    /// wr.margins.left = 2;
+    ///
+    /// // Text is wrapped in several blocks, the margins surround all of them
+    /// let mut rows = Vec::new();
+    /// wr.try_wrap_text("hello world", &mut |v| { rows.extend_from_slice(v); Ok(()) })?;
+    /// for line in wr.finish(&rows)? {
+    ///     println!("{}", line);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If `height` is set and the output has more rows, no rows are dropped and a
+    /// TooTall error is returned.
+    pub fn finish(&self, rows: &[String]) -> Result<Vec<String>, Error> {
+        let mut rows = rows.to_vec();
+
+        if let Some(height) = self.height {
+            if rows.len() > height {
+                return Err(Error::TooTall(height));
+            }
+            let n = height - rows.len();
+            let top = match self.valign {
+                VAlign::Top    => 0,
+                VAlign::Middle => n / 2,
+                VAlign::Bottom => n,
+            };
+            rows.splice(0..0, (0..top).map(|_| String::new()));
+            rows.extend((top..n).map(|_| String::new()));
+        }
+
+        // the frame is drawn after alignment, around the whole text width
+        if let Some(ref frame) = self.frame {
+            rows = frame.draw(&rows, self.text_width());
         }
 
-        let w = self.width - self.len();
-        let v = self.sm.get();
+        let mut v: Vec<String> = (0..self.margins.top).map(|_| String::new()).collect();
+        v.extend(rows);
+        v.extend((0..self.margins.bottom).map(|_| String::new()));
+        let style = self.row_style();
+        Ok(v.into_iter().map(|x| style.apply(x)).collect())
+    }
+
+    // Whether finish changes the number of rows, so the whole output must be collected
+    // before it is finished.
+    pub(crate) fn has_box(&self) -> bool {
+        self.height.is_some() || self.frame.is_some() || self.margins.top > 0 ||
+            self.margins.bottom > 0
+    }
+
+    /// Get the number of columns available for text, that is, the maximum width minus
+    /// the left and right margins, the frame and the comment delimiters, if any.
+    pub fn text_width(&self) -> usize {
+        let frame = self.frame.as_ref().map_or(0, |x| x.width());
+        self.box_width().saturating_sub(self.margins.left + self.margins.right + frame)
+    }
+
+    // Width of rows before the comment delimiters are added.
+    fn box_width(&self) -> usize {
+        self.width.saturating_sub(self.comment.map_or(0, |x| x.width()))
+    }
 
-        match self.align {
-            Align::Left   => v,
-            Align::Center => add_pad(v, w / 2),
-            Align::Right  => add_pad(v, w),
+    // Get the decorations finish adds to each row.
+    pub(crate) fn row_style(&self) -> RowStyle {
+        RowStyle{
+            left   : self.margins.left,
+            fill   : match self.fill { true => Some(self.box_width()), false => None },
+            trim   : self.trim_trailing,
+            comment: self.comment,
         }
     }
 
-    /// Get the length in sub-characters of the current output buffer.
+    /// Get the display width, in terminal columns, of the current line in the output buffer.
     pub fn len(&self) -> usize {
         self.sm.len()
     }
 
+    /// Verify whether a string can be added to the output buffer without making the line
+    /// wider than the maximum number of columns. The output buffer is not changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let mut wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 80);
+    ///
+    /// wr.push_str("hello")?;
+    /// if wr.fits(" world") {
+    ///     wr.push_str(" world")?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn fits(&self, s: &str) -> bool {
+        self.sm.measure(s) <= self.text_width()
+    }
+
     /// Verify whether the output buffer is empty.
     pub fn is_empty(&self) -> bool {
         self.sm.is_empty()
@@ -105,16 +265,14 @@ impl<'a> Wrapper<'a> {
     /// # Errors
     ///
     /// If adding the string results in a line wider than the maximum number of columns,
-    /// the string is not added to the output buffer and a LineFull error is returned.
+    /// including lines finished by newlines in the string, the string is not added to the
+    /// output buffer and a LineFull error is returned.
     pub fn push_str(&mut self, s: &str) -> Result<(), Error> {
-        self.sm.push_str(s);
-
-        if self.sm.len() > self.width {
-            self.sm.clear();
-            self.sm.push_str(&self.buffer);
+        if !self.fits(s) {
             return Err(Error::LineFull)
         }
 
+        self.sm.push_str(s);
         self.buffer.push_str(s);
         Ok(())
     }
@@ -126,25 +284,74 @@ impl<'a> Wrapper<'a> {
     /// If adding the character results in a line wider than the maximum number of columns,
     /// the character is not added to the output buffer and a LineFull error is returned.
     pub fn push(&mut self, ch: char) -> Result<(), Error> {
-        self.sm.push(ch);
-
-        if self.sm.len() > self.width {
-            self.sm.clear();
-            self.sm.push_str(&self.buffer);
+        if !self.fits(ch.encode_utf8(&mut [0; 4])) {
             return Err(Error::LineFull)
         }
 
+        self.sm.push(ch);
         self.buffer.push(ch);
         Ok(())
     }
 
+    /// Remove the last character from the output buffer. Returns the removed character, or
+    /// None if the buffer is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let mut wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 80);
+    ///
+    /// // Handle a backspace typed by the user
+    /// wr.push_str("hello")?;
+    /// wr.pop();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn pop(&mut self) -> Option<char> {
+        let ch = self.buffer.pop()?;
+        self.sm.pop();
+        self.has_space = self.buffer.is_empty() || self.buffer.ends_with(char::is_whitespace);
+        Some(ch)
+    }
+
     /// Add a string to the output buffer, wrapping it if necessary.
     ///
     /// If the new string causes the output to be wider than the maximum width, the current
     /// buffer contents (if any) will be passed to the flush callback, the buffer will be
     /// cleared, and the new string will be added to the buffer. If the string is wider
-    /// than the output buffer, it will be wrapped at character level.
+    /// than the output buffer, it will be wrapped at character level. Newlines in the
+    /// string flush the buffer.
     pub fn wrap_str(&mut self, s: &str, flush: &Fn(&Vec<String>)) {
+        let _ = self.try_wrap_str(s, &mut |v| { flush(&v.to_vec()); Ok(()) });
+    }
+
+    /// Add a string to the output buffer, wrapping it if necessary, with a fallible flush
+    /// callback.
+    ///
+    /// This works like `wrap_str`, but the flush callback can fail (e.g. when writing the
+    /// flushed lines to a stream).
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by the flush callback are passed on to the caller. The string will
+    /// be partially added to the output buffer in this case.
+    pub fn try_wrap_str(&mut self, s: &str, flush: &mut dyn FnMut(&[String]) -> Result<(), Error>)
+                        -> Result<(), Error> {
+
+        if s.contains('\n') {
+            for (i, part) in s.split('\n').enumerate() {
+                if i > 0 {
+                    flush(&self.get())?;
+                    self.clear_buffer();
+                }
+                if !part.is_empty() {
+                    self.try_wrap_str(part, flush)?;
+                }
+            }
+            return Ok(());
+        }
 
         let empty = s.trim().is_empty();
 
@@ -156,16 +363,18 @@ impl<'a> Wrapper<'a> {
 
         if self.push_str(s).is_err() {
             if !self.buffer.is_empty() {
-                flush(&self.get());
-                self.clear();
+                flush(&self.get())?;
+                self.clear_buffer();
             }
             if self.push_str(s).is_err() {
-                self.wrap_word(s, flush)
+                self.try_wrap_word(s, flush)?;
             }
             self.has_space = false;
         }
+
+        Ok(())
     }
-    
+
     /// Add a word to the output buffer, breaking it if necessary.
     ///
     /// Add this word to the output character by character. If a new character causes the
@@ -174,45 +383,455 @@ impl<'a> Wrapper<'a> {
     /// will be added to the buffer. If the character is wider than the maximum width, it
     /// will be added without any additional processing.
     pub fn wrap_word(&mut self, word: &str, flush: &Fn(&Vec<String>)) {
+        let _ = self.try_wrap_word(word, &mut |v| { flush(&v.to_vec()); Ok(()) });
+    }
+
+    /// Add a word to the output buffer, breaking it if necessary, with a fallible flush
+    /// callback.
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by the flush callback are passed on to the caller.
+    pub fn try_wrap_word(&mut self, word: &str, flush: &mut dyn FnMut(&[String]) -> Result<(), Error>)
+                         -> Result<(), Error> {
         for c in word.chars() {
             if self.push(c).is_err() {
                 if !self.buffer.is_empty() {
-                    flush(&self.get());
-                    self.clear();
+                    flush(&self.get())?;
+                    self.clear_buffer();
                 }
                 // don't wrap this character
                 self.sm.push(c);
                 self.buffer.push(c);
             }
         }
+        Ok(())
+    }
+
+    /// Add a line of text as part of a paragraph, wrapping it if necessary.
+    ///
+    /// Line breaks inside a paragraph are soft: consecutive lines are joined as if they
+    /// were separated by a space. A blank line ends the current paragraph, and a line
+    /// starting with whitespace ends the current paragraph and starts a new one. Each
+    /// new paragraph after the first is preceded by `paragraph_spacing` blank rows.
+    /// Call `end_paragraph` after the last line to flush the pending output.
+    ///
+    /// Alignment is applied when lines are flushed, so it can be changed between
+    /// paragraphs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # fn foo() -> Result<(), Box<std::error::Error>> {
+    /// let font = rustlet::FIGfont::from_path("small.flf")?;
+    /// let mut wr = rustlet::Wrapper::new(rustlet::Smusher::new(&font), 80);
+    /// wr.paragraph_spacing = 1;
+    ///
+    /// let print = |v: &[String]| v.iter().for_each(|x| println!("{}", x));
+    /// wr.wrap_paragraph("hello", &print);
+    /// wr.wrap_paragraph("world", &print);
+    /// wr.wrap_paragraph("", &print);
+    ///
+    /// // The next paragraph is centered
+    /// wr.align = rustlet::Align::Center;
+    /// wr.wrap_paragraph("goodbye", &print);
+    /// wr.end_paragraph(&print);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wrap_paragraph(&mut self, line: &str, flush: &dyn Fn(&[String])) {
+        let _ = self.try_wrap_paragraph(line, &mut |v| { flush(v); Ok(()) });
+    }
+
+    /// Add a line of text as part of a paragraph, wrapping it if necessary, with a
+    /// fallible flush callback.
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by the flush callback are passed on to the caller.
+    pub fn try_wrap_paragraph(&mut self, line: &str, flush: &mut dyn FnMut(&[String]) -> Result<(), Error>)
+                              -> Result<(), Error> {
+        if line.trim().is_empty() {
+            return self.try_end_paragraph(flush);
+        }
+
+        if line.starts_with(char::is_whitespace) {
+            self.try_end_paragraph(flush)?;
+        }
+
+        if !self.in_para {
+            if self.paragraphs > 0 && self.paragraph_spacing > 0 {
+                flush(&vec![String::new(); self.paragraph_spacing])?;
+            }
+            self.in_para = true;
+        }
+
+        for token in tokens(line) {
+            self.try_wrap_str(token, flush)?;
+        }
+        Ok(())
+    }
+
+    /// Finish the current paragraph, passing the pending output to the flush callback.
+    /// Does nothing if no paragraph is being rendered.
+    pub fn end_paragraph(&mut self, flush: &dyn Fn(&[String])) {
+        let _ = self.try_end_paragraph(&mut |v| { flush(v); Ok(()) });
+    }
+
+    /// Finish the current paragraph, with a fallible flush callback.
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by the flush callback are passed on to the caller.
+    pub fn try_end_paragraph(&mut self, flush: &mut dyn FnMut(&[String]) -> Result<(), Error>)
+                             -> Result<(), Error> {
+        if !self.in_para {
+            return Ok(());
+        }
+
+        self.in_para = false;
+        self.paragraphs += 1;
+        if !self.is_empty() {
+            let v = self.get();
+            self.clear_buffer();
+            flush(&v)?;
+        }
+        Ok(())
+    }
+
+    /// Add text made of one or more paragraphs, wrapping it if necessary. Lines are
+    /// handled as in `wrap_paragraph`, and the last paragraph is ended.
+    pub fn wrap_text(&mut self, text: &str, flush: &dyn Fn(&[String])) {
+        let _ = self.try_wrap_text(text, &mut |v| { flush(v); Ok(()) });
+    }
+
+    /// Add text made of one or more paragraphs, wrapping it if necessary, with a
+    /// fallible flush callback.
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by the flush callback are passed on to the caller.
+    pub fn try_wrap_text(&mut self, text: &str, flush: &mut dyn FnMut(&[String]) -> Result<(), Error>)
+                         -> Result<(), Error> {
+        for line in text.lines() {
+            self.try_wrap_paragraph(line, flush)?;
+        }
+        self.try_end_paragraph(flush)
     }
 }
 
 
-fn add_pad(v: Vec<String>, pad_size: usize) -> Vec<String> {
-    fn pad(num: usize) -> String {
-        (0..num).map(|_| " ").collect::<String>()
+// Split a string in runs of whitespace and non-whitespace characters.
+pub(crate) fn tokens(s: &str) -> Vec<&str> {
+    let mut v = Vec::new();
+    let mut start = 0;
+    let mut space = None;
+    for (i, c) in s.char_indices() {
+        let ws = c.is_whitespace();
+        if space.is_some() && space != Some(ws) {
+            v.push(&s[start..i]);
+            start = i;
+        }
+        space = Some(ws);
+    }
+    if start < s.len() {
+        v.push(&s[start..]);
+    }
+    v
+}
+
+// The decorations added to each finished row, copied from a wrapper so rows can be
+// finished while the wrapper is borrowed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RowStyle {
+    left   : usize,                // left margin
+    fill   : Option<usize>,        // width rows are padded to, if rows are filled
+    trim   : bool,                 // whether trailing blanks are removed
+    comment: Option<CommentStyle>, // comment syntax wrapped around the row
+}
+
+impl RowStyle {
+    // Add the left margin, padding and comment delimiters to a row of text already
+    // aligned and framed. Blank rows are only padded if rows are filled.
+    pub(crate) fn apply(&self, row: String) -> String {
+        let mut row = match row.is_empty() && self.fill.is_none() {
+            true  => row,
+            false => add_pad(row, self.left),
+        };
+        if let Some(width) = self.fill {
+            let w = width - min(cell::str_width(&row), width);
+            row.extend((0..w).map(|_| ' '));
+        }
+        if self.trim {
+            let n = row.trim_end().len();
+            row.truncate(n);
+        }
+        match self.comment {
+            Some(comment) => comment.apply(&row),
+            None          => row,
+        }
     }
-    v.iter().map(|x| pad(pad_size) + x).collect()
+}
+
+fn trim(row: &str, width: usize) -> String {
+    cell::truncate(row, width)
+}
+
+fn add_pad(row: String, pad_size: usize) -> String {
+    (0..pad_size).map(|_| " ").collect::<String>() + &row
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use FIGfont;
+
+    fn test_font() -> FIGfont {
+        let path = env!("CARGO_MANIFEST_DIR").to_owned() + "/tests/test.flf";
+        FIGfont::from_path(&path).unwrap()
+    }
+
+    fn collect(wr: &mut Wrapper<&FIGfont>, text: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        wr.try_wrap_text(text, &mut |v| { lines.extend_from_slice(v); Ok(()) }).unwrap();
+        lines
+    }
+
+    fn finished(wr: &mut Wrapper<&FIGfont>) -> Vec<String> {
+        let rows = wr.get();
+        wr.finish(&rows).unwrap()
+    }
+
+    #[test]
+    fn test_push_newline() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 4);
+        assert!(wr.push_str("abcdefgh\nx").is_err());
+        assert!(wr.is_empty());
+        assert_eq!(wr.get(), vec![""]);
+
+        wr.push_str("abcd\nx").unwrap();
+        assert!(wr.push_str("\nabcdefgh").is_err());
+        assert_eq!(wr.get(), vec!["abcd", "x"]);
+    }
+
+    #[test]
+    fn test_fits_newline() {
+        let font = test_font();
+        let wr = Wrapper::new(Smusher::new(&font), 4);
+        assert!(wr.fits("abcd\nx"));
+        assert!(!wr.fits("abcdefgh\nx"));
+        assert!(!wr.fits("x\nabcdefgh"));
+    }
 
-    macro_rules! vec_string {
-        ( $($x:expr),* ) => (vec![$($x.to_string()),*])
+    #[test]
+    fn test_tokens() {
+        assert_eq!(tokens(""), Vec::<&str>::new());
+        assert_eq!(tokens("this is  a"), vec!["this", " ", "is", "  ", "a"]);
+        assert_eq!(tokens(" áé\tíó "), vec![" ", "áé", "\t", "íó", " "]);
+    }
+
+    #[test]
+    fn test_trim() {
+        assert_eq!(trim("12345", 3), "123");
+        assert_eq!(trim("áéíóú", 3), "áéí");
+        assert_eq!(trim("12", 3), "12");
+        assert_eq!(trim("áé", 3), "áé");
+        assert_eq!(trim("e\u{301}e\u{301}e\u{301}", 3), "e\u{301}e\u{301}e\u{301}");
+        assert_eq!(trim("全角全", 3), "全 ");
     }
 
     #[test]
     fn test_padding() {
-        assert_eq!(add_pad(vec_string!("x", "x"), 0), vec_string!("x", "x"));
-        assert_eq!(add_pad(vec_string!("x", "x"), 4), vec_string!("    x", "    x"));
+        assert_eq!(add_pad("x".to_string(), 0), "x");
+        assert_eq!(add_pad("x".to_string(), 4), "    x");
     }
 
     #[test]
     fn test_padding_utf8() {
-        assert_eq!(add_pad(vec_string!("á", "á"), 0), vec_string!("á", "á"));
-        assert_eq!(add_pad(vec_string!("á", "á"), 4), vec_string!("    á", "    á"));
+        assert_eq!(add_pad("á".to_string(), 0), "á");
+        assert_eq!(add_pad("á".to_string(), 4), "    á");
+    }
+
+    #[test]
+    fn test_newline_align() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 6);
+        wr.align = Align::Right;
+        wr.push_str("abc\nde").unwrap();
+        assert_eq!(wr.len(), 2);
+        assert_eq!(wr.get(), vec!["   abc", "    de"]);
+    }
+
+    #[test]
+    fn test_wrap_newline() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 20);
+        let mut lines = Vec::new();
+        wr.try_wrap_str("ab\n\ncd", &mut |v| { lines.extend_from_slice(v); Ok(()) }).unwrap();
+        assert_eq!(lines, vec!["ab", ""]);
+        assert_eq!(wr.get(), vec!["cd"]);
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 12);
+        let lines = collect(&mut wr, "this is\na test\n\n\nnew\n indented");
+        assert_eq!(lines, vec!["this is a ", "test", "new", " indented"]);
+        assert!(wr.is_empty());
+    }
+
+    #[test]
+    fn test_paragraph_spacing() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 12);
+        wr.paragraph_spacing = 2;
+        let lines = collect(&mut wr, "\nab\ncd\n\nef\n");
+        assert_eq!(lines, vec!["ab cd", "", "", "ef"]);
+    }
+
+    #[test]
+    fn test_paragraph_clear() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 12);
+        wr.paragraph_spacing = 1;
+        assert_eq!(collect(&mut wr, "ab\n\ncd"), vec!["ab", "", "cd"]);
+
+        // the first paragraph after clear is not preceded by spacing rows
+        wr.clear();
+        assert_eq!(collect(&mut wr, "ef\n\ngh"), vec!["ef", "", "gh"]);
+    }
+
+    #[test]
+    fn test_paragraph_align() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 6);
+        let mut lines = Vec::new();
+        {
+            let mut flush = |v: &[String]| { lines.extend_from_slice(v); Ok(()) };
+            wr.try_wrap_paragraph("ab", &mut flush).unwrap();
+            wr.try_end_paragraph(&mut flush).unwrap();
+            wr.align = Align::Right;
+            wr.try_wrap_paragraph("cd", &mut flush).unwrap();
+            wr.try_end_paragraph(&mut flush).unwrap();
+        }
+        assert_eq!(lines, vec!["ab", "    cd"]);
+    }
+
+    #[test]
+    fn test_margins() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 8);
+        wr.margins = Margins{ top: 1, right: 2, bottom: 1, left: 2 };
+        assert_eq!(wr.text_width(), 4);
+        assert!(wr.push_str("abcde").is_err());
+        wr.push_str("abc").unwrap();
+        assert_eq!(finished(&mut wr), vec!["", "  abc", ""]);
+
+        wr.fill = true;
+        assert_eq!(finished(&mut wr), vec!["        ", "  abc   ", "        "]);
+
+        wr.align = Align::Right;
+        wr.trim_trailing = true;
+        assert_eq!(finished(&mut wr), vec!["", "   abc", ""]);
+    }
+
+    #[test]
+    fn test_margins_blocks() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 6);
+        wr.margins = Margins{ top: 1, right: 0, bottom: 1, left: 2 };
+        wr.height = Some(5);
+        wr.paragraph_spacing = 1;
+
+        let mut rows = Vec::new();
+        wr.try_wrap_paragraph("ab cd", &mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        wr.try_wrap_paragraph("", &mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        wr.try_wrap_paragraph("ef", &mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        wr.try_end_paragraph(&mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        assert_eq!(rows, vec!["ab ", "cd", "", "ef"]);
+
+        // margins and height apply once to the whole output, not to each block
+        assert_eq!(wr.finish(&rows).unwrap(), vec!["", "  ab ", "  cd", "", "  ef", "", ""]);
+
+        wr.height = Some(3);
+        match wr.finish(&rows) {
+            Err(Error::TooTall(3)) => (),
+            _                      => panic!("expected TooTall error"),
+        }
+    }
+
+    #[test]
+    fn test_fixed_height() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 4);
+        wr.fill = true;
+        wr.height = Some(4);
+        wr.push_str("ab\ncd").unwrap();
+        assert_eq!(finished(&mut wr), vec!["ab  ", "cd  ", "    ", "    "]);
+
+        assert_eq!(wr.valign, VAlign::Top);
+        wr.valign = VAlign::Middle;
+        assert_eq!(finished(&mut wr), vec!["    ", "ab  ", "cd  ", "    "]);
+
+        wr.valign = VAlign::Bottom;
+        assert_eq!(finished(&mut wr), vec!["    ", "    ", "ab  ", "cd  "]);
+
+        wr.height = Some(1);
+        let rows = wr.get();
+        assert!(wr.finish(&rows).is_err());
+    }
+
+    #[test]
+    fn test_frame() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 10);
+        wr.margins.left = 1;
+        wr.frame = Some(Frame::new(::FrameStyle::Ascii));
+        wr.align = Align::Center;
+        assert_eq!(wr.text_width(), 7);
+        wr.push_str("abc").unwrap();
+        assert!(wr.push_str("defgh").is_err());
+        assert_eq!(finished(&mut wr), vec![" +-------+", " |  abc  |", " +-------+"]);
+
+        wr.height = Some(2);
+        wr.valign = VAlign::Bottom;
+        assert_eq!(finished(&mut wr), vec![" +-------+", " |       |", " |  abc  |", " +-------+"]);
+    }
+
+    #[test]
+    fn test_frame_blocks() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 6);
+        wr.frame = Some(Frame::new(::FrameStyle::Ascii));
+        wr.paragraph_spacing = 1;
+
+        let mut rows = Vec::new();
+        wr.try_wrap_paragraph("ab", &mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        wr.try_wrap_paragraph("", &mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        wr.try_wrap_paragraph("cd", &mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+        wr.try_end_paragraph(&mut |v| { rows.extend_from_slice(v); Ok(()) }).unwrap();
+
+        // a single frame encloses all blocks and the spacing between paragraphs
+        assert_eq!(wr.finish(&rows).unwrap(), vec!["+----+", "|ab  |", "|    |", "|cd  |", "+----+"]);
+    }
+
+    #[test]
+    fn test_comment() {
+        let font = test_font();
+        let mut wr = Wrapper::new(Smusher::new(&font), 10);
+        wr.comment = Some(CommentStyle::CBlock);
+        assert_eq!(wr.text_width(), 4);
+        wr.push_str("a/*b").unwrap();
+        assert_eq!(finished(&mut wr), vec!["/* a/+b */"]);
+
+        wr.clear();
+        wr.comment = Some(CommentStyle::Hash);
+        wr.margins.top = 1;
+        wr.push_str("ab").unwrap();
+        assert_eq!(finished(&mut wr), vec!["#", "# ab"]);
+        wr.fill = true;
+        assert_eq!(finished(&mut wr), vec!["#         ", "# ab      "]);
     }
 }
@@ -7,7 +7,7 @@ macro_rules! new_smusher {
         let path = env!("CARGO_MANIFEST_DIR").to_owned() + &path::MAIN_SEPARATOR.to_string() + $b;
         let font = rustlet::FIGfont::from_path(path).unwrap();
         let mut $a = rustlet::Smusher::new(&font);
-        $a.mode = font.layout;
+        $a.mode = rustlet::LayoutMode::from_full_layout(font.layout);
     }
 }
 
@@ -26,6 +26,21 @@ fn line_full() {
     assert_eq!(wr.get(), vec!["this is "]);
 }
 
+#[test]
+fn backspace() {
+    new_smusher!(sm, "fonts/small.flf");
+    let mut wr = rustlet::Wrapper::new(sm, 60);
+    assert!(wr.push_str("Smushy").is_ok());
+    assert_eq!(wr.pop(), Some('y'));
+    assert_eq!(wr.pop(), Some('h'));
+    assert!(wr.push('s').is_ok());
+    assert_eq!(wr.get(), vec![r" ___                 ",
+                              r"/ __|_ __ _  _ ______",
+                              r"\__ \ '  \ || (_-<_-<",
+                              r"|___/_|_|_\_,_/__/__/",
+                              r"                     "]);
+}
+
 #[test]
 fn line_wrap() {
     new_smusher!(sm, "tests/test.flf");
@@ -89,7 +104,7 @@ fn smushing() {
 #[test]
 fn kerning() {
     new_smusher!(sm, "fonts/small.flf");
-    sm.mode = rustlet::SMUSH_KERN;
+    sm.mode = rustlet::LayoutMode::Fitting;
     let mut wr = rustlet::Wrapper::new(sm, 60);
     assert!(!wr.push_str("Kerning").is_err());
     assert_eq!(wr.get(), vec![r" _  __                 _             ",
@@ -102,7 +117,7 @@ fn kerning() {
 #[test]
 fn overlap() {
     new_smusher!(sm, "fonts/standard.flf");
-    sm.mode = 0;
+    sm.mode = rustlet::LayoutMode::Universal;
     let mut wr = rustlet::Wrapper::new(sm, 60);
     assert!(!wr.push_str("Over Write").is_err());
     assert_eq!(wr.get(), vec![r"  ___                  __        __    _ _       ",
@@ -116,7 +131,7 @@ fn overlap() {
 #[test]
 fn full_width() {
     new_smusher!(sm, "fonts/small.flf");
-    sm.full_width = true;
+    sm.mode = rustlet::LayoutMode::FullWidth;
     let mut wr = rustlet::Wrapper::new(sm, 60);
     assert!(!wr.push_str("Full width").is_err());
     assert_eq!(wr.get(), vec![r"  ___          _   _              _      _   _     _    ",
@@ -139,3 +154,46 @@ fn utf8_input() {
                               r"         )_)                     |_____|       "]);
 }
 
+
+#[test]
+fn wide_characters() {
+    new_smusher!(sm, "tests/wide.tlf");
+    sm.push_str("A*eB");
+    assert_eq!(sm.len(), 8);
+    assert_eq!(sm.get(), vec!["全😀e\u{301}e\u{301}角",
+                              "AA**eeBB"]);
+}
+
+#[test]
+fn wide_characters_smushed() {
+    new_smusher!(sm, "tests/wide.tlf");
+    sm.mode = rustlet::LayoutMode::Universal;
+    sm.push_str("AA");
+    assert_eq!(sm.len(), 3);
+    assert_eq!(sm.get(), vec![" 全",
+                              "AAA"]);
+}
+
+#[test]
+fn wide_characters_wrap() {
+    new_smusher!(sm, "tests/wide.tlf");
+    let mut wr = rustlet::Wrapper::new(sm, 5);
+    wr.align = rustlet::Align::Right;
+    assert!(wr.push_str("AB").is_ok());
+    assert!(wr.push('A').is_err());
+    assert_eq!(wr.get(), vec![" 全角",
+                              " AABB"]);
+
+    wr.width = 3;
+    assert_eq!(wr.get(), vec!["全 ",
+                              "AAB"]);
+}
+
+#[test]
+fn private_use_characters() {
+    new_smusher!(sm, "tests/wide.tlf");
+    sm.push_str("\u{e000}A");
+    assert_eq!(sm.len(), 4);
+    assert_eq!(sm.get(), vec!["\u{10abcd}\u{10fffd}全",
+                              "PPAA"]);
+}